@@ -1,5 +1,5 @@
 use log::warn;
-use mxc::replay_gain::{album_rg, ReplayGain};
+use mxc::replay_gain::{album_rg, ReplayGain, Target};
 use mxc::AudioFile;
 use options::LoudgainOpts;
 use record::Record;
@@ -35,7 +35,7 @@ pub fn loudgain(opts: LoudgainOpts, total_quiet: bool) -> Vec<Record> {
 
     files
         .iter_mut()
-        .try_for_each(|x| x.track_gain(opts.pre_gain, false))
+        .try_for_each(|x| x.track_gain(Target::from_pregain(opts.pre_gain), false))
         .unwrap();
 
     let mut records: Vec<Record> = files
@@ -44,7 +44,7 @@ pub fn loudgain(opts: LoudgainOpts, total_quiet: bool) -> Vec<Record> {
         .collect();
 
     let album: Option<ReplayGain> = if opts.do_album {
-        let mut album_rg = album_rg(&files, opts.pre_gain).unwrap();
+        let mut album_rg = album_rg(&files, Target::from_pregain(opts.pre_gain)).unwrap();
         records.push(Record::new_album(album_rg, opts.unit.clone()));
         records.last_mut().unwrap().fill(
             album_rg.clipper(opts.max_true_peak_level, opts.clip_prevention),
@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use mxc::replay_gain::{lufs_to_dbtp, Clipper, ReplayGain};
+use mxc::replay_gain::{linear_to_db, Clipper, ReplayGain};
 use mxc::AudioFile;
 
 use crate::options::OutputMode;
@@ -144,7 +144,7 @@ impl Record {
             Loudness: Num::newr(rg.loudness, "LUFS", 2),
             Range: Num::newr(rg.loudness_range, &unit, 2),
             True_Peak: round(rg.peak, 6),
-            True_Peak_dBTP: Num::newr(lufs_to_dbtp(rg.peak), "dBTP", 2),
+            True_Peak_dBTP: Num::newr(linear_to_db(rg.peak), "dBTP", 2),
             Reference: Num::newr(rg.loudness_reference, "LUFS", 2),
             Will_clip: false,
             Clip_prevent: false,
@@ -160,7 +160,7 @@ impl Record {
         self.Clip_prevent = clip_prevented;
         self.Gain = Num::newr(rg.gain, &unit, 2);
         self.New_Peak = round(new_peak, 6);
-        self.New_Peak_dBTP = Num::newr(lufs_to_dbtp(new_peak), "dBTP", 2);
+        self.New_Peak_dBTP = Num::newr(linear_to_db(new_peak), "dBTP", 2);
     }
 }
 
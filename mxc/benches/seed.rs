@@ -0,0 +1,97 @@
+//! Seeding throughput per decoded sample format.
+//!
+//! Synthetic WAV files are generated in temp dir, so no test data is needed.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mxc::options::{Decoder, Tagger};
+use mxc::{AudioFile, NONE};
+
+const RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+const SECONDS: u32 = 10;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// (name, wav format tag, bits per sample, sample encoder)
+type Format = (&'static str, u16, u16, fn(f64) -> Vec<u8>);
+
+const FORMATS: [Format; 5] = [
+    ("u8", WAVE_FORMAT_PCM, 8, |x| {
+        vec![(x * f64::from(i8::MAX) + 128.0) as u8]
+    }),
+    ("i16", WAVE_FORMAT_PCM, 16, |x| {
+        ((x * f64::from(i16::MAX)) as i16).to_le_bytes().to_vec()
+    }),
+    ("i32", WAVE_FORMAT_PCM, 32, |x| {
+        ((x * f64::from(i32::MAX)) as i32).to_le_bytes().to_vec()
+    }),
+    ("f32", WAVE_FORMAT_IEEE_FLOAT, 32, |x| {
+        (x as f32).to_le_bytes().to_vec()
+    }),
+    ("f64", WAVE_FORMAT_IEEE_FLOAT, 64, |x| {
+        x.to_le_bytes().to_vec()
+    }),
+];
+
+/// Writes stereo sine (different frequency per channel) as WAV
+fn write_wav(name: &str, format: u16, bits: u16, encode: fn(f64) -> Vec<u8>) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mxc-bench-{name}.wav"));
+    let frames = RATE * SECONDS;
+    let block_align = CHANNELS * bits / 8;
+    let data_len = frames * u32::from(block_align);
+
+    let mut w = BufWriter::new(File::create(&path).unwrap());
+    w.write_all(b"RIFF").unwrap();
+    w.write_all(&(36 + data_len).to_le_bytes()).unwrap();
+    w.write_all(b"WAVEfmt ").unwrap();
+    w.write_all(&16u32.to_le_bytes()).unwrap();
+    w.write_all(&format.to_le_bytes()).unwrap();
+    w.write_all(&CHANNELS.to_le_bytes()).unwrap();
+    w.write_all(&RATE.to_le_bytes()).unwrap();
+    w.write_all(&(RATE * u32::from(block_align)).to_le_bytes())
+        .unwrap();
+    w.write_all(&block_align.to_le_bytes()).unwrap();
+    w.write_all(&bits.to_le_bytes()).unwrap();
+    w.write_all(b"data").unwrap();
+    w.write_all(&data_len.to_le_bytes()).unwrap();
+    for i in 0..frames {
+        let t = f64::from(i) / f64::from(RATE);
+        for c in 0..CHANNELS {
+            let freq = 440.0 * f64::from(c + 1);
+            let x = 0.5 * (2.0 * std::f64::consts::PI * freq * t).sin();
+            w.write_all(&encode(x)).unwrap();
+        }
+    }
+    w.flush().unwrap();
+    path
+}
+
+fn seed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seed");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(u64::from(
+        RATE * SECONDS * u32::from(CHANNELS),
+    )));
+    for (name, format, bits, encode) in FORMATS {
+        let path = write_wav(name, format, bits, encode);
+        for (analyzers, ebur, dr_meter) in [("none", false, false), ("all", true, true)] {
+            group.bench_with_input(BenchmarkId::new(analyzers, name), &path, |b, path| {
+                b.iter(|| {
+                    let mut af =
+                        AudioFile::with_backends(path, Decoder::FFmpeg, Tagger::default()).unwrap();
+                    af.seed(ebur, dr_meter, false, NONE).unwrap();
+                })
+            });
+        }
+        let _ = std::fs::remove_file(path);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, seed);
+criterion_main!(benches);
@@ -0,0 +1,243 @@
+use std::fmt::Display;
+
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+/// Samples at or above this (absolute, normalized) level are considered at full scale
+pub const FULL_SCALE_THRESHOLD: f64 = 0.999;
+
+/// Consecutive samples at full scale that are considered clipped run
+pub const MIN_CLIPPED_RUN: u64 = 3;
+
+/// Only this many clipped regions are recorded
+const MAX_REGIONS: usize = 1000;
+
+/// Clipping statistics of one channel
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelClipping {
+    /// Sample peak (normalized, 1.0 is full scale)
+    pub peak: f64,
+    /// Samples at or near full scale
+    pub near_full_scale: u64,
+    /// Runs of at least [MIN_CLIPPED_RUN] consecutive samples at full scale
+    pub runs: u64,
+    /// Longest run of samples at full scale
+    pub longest_run: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClippingStats {
+    pub channels: Vec<ChannelClipping>,
+    /// Clipped regions as (start, end) in seconds (only first 1000 are recorded)
+    pub regions: Vec<(f64, f64)>,
+}
+
+impl ClippingStats {
+    /// Sample peak of all channels
+    pub fn peak(&self) -> f64 {
+        self.channels.iter().map(|c| c.peak).fold(0.0, f64::max)
+    }
+
+    pub fn near_full_scale(&self) -> u64 {
+        self.channels.iter().map(|c| c.near_full_scale).sum()
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.channels.iter().map(|c| c.runs).sum()
+    }
+
+    pub fn longest_run(&self) -> u64 {
+        self.channels
+            .iter()
+            .map(|c| c.longest_run)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn is_clipped(&self) -> bool {
+        self.runs() > 0
+    }
+}
+
+impl Display for ClippingStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sample peak {:.2} dBFS, {} samples near full scale, {} clipped runs (longest {})",
+            20.0 * self.peak().log10(),
+            self.near_full_scale(),
+            self.runs(),
+            self.longest_run()
+        )?;
+        // first few are enough to find them
+        for (i, (start, end)) in self.regions.iter().take(5).enumerate() {
+            let sep = if i == 0 { " at" } else { "," };
+            write!(f, "{sep} {start:.3}s-{end:.3}s")?;
+        }
+        if self.regions.len() > 5 {
+            write!(f, ", ...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Inserts region into regions sorted by start, merging it with overlapping ones
+/// (runs of different channels end out of order). Only first [MAX_REGIONS] are kept.
+fn add_region(regions: &mut Vec<(f64, f64)>, start: f64, end: f64) {
+    let mut i = regions.partition_point(|r| r.0 <= start);
+    if i > 0 && start <= regions[i - 1].1 {
+        i -= 1;
+        regions[i].1 = regions[i].1.max(end);
+    } else if i < MAX_REGIONS {
+        regions.insert(i, (start, end));
+    } else {
+        return;
+    }
+    // new end could reach following regions
+    while i + 1 < regions.len() && regions[i + 1].0 <= regions[i].1 {
+        let (_, next_end) = regions.remove(i + 1);
+        regions[i].1 = regions[i].1.max(next_end);
+    }
+    regions.truncate(MAX_REGIONS);
+}
+
+/// Sample peak and clipping analyzer (finds brickwalled masters)
+pub struct Clipping {
+    rate: u32,
+    channels: usize,
+    threshold: f64,
+    /// instants (samples per channel) seen so far
+    position: u64,
+    /// start of current run at full scale per channel
+    run_start: Vec<Option<u64>>,
+    stats: ClippingStats,
+    /// Available after seeding
+    pub result: Option<ClippingStats>,
+}
+
+impl Clipping {
+    pub fn new(info: &AudioInfo) -> Self {
+        let channels = info.channels as usize;
+        Self {
+            rate: info.rate,
+            channels,
+            threshold: FULL_SCALE_THRESHOLD,
+            position: 0,
+            run_start: vec![None; channels],
+            stats: ClippingStats {
+                channels: vec![ChannelClipping::default(); channels],
+                regions: Vec::new(),
+            },
+            result: None,
+        }
+    }
+
+    /// Use custom full scale threshold (normalized) instead of [FULL_SCALE_THRESHOLD]
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Closes run of channel `c` that ended before `end`
+    fn end_run(&mut self, c: usize, end: u64) {
+        if let Some(start) = self.run_start[c].take() {
+            let len = end - start;
+            let ch = &mut self.stats.channels[c];
+            ch.longest_run = ch.longest_run.max(len);
+            if len >= MIN_CLIPPED_RUN {
+                ch.runs += 1;
+                let rate = f64::from(self.rate);
+                add_region(
+                    &mut self.stats.regions,
+                    start as f64 / rate,
+                    end as f64 / rate,
+                );
+            }
+        }
+    }
+}
+
+impl Analyzer for Clipping {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        for_each_sample(frame, self.channels, |c, x| {
+            let x = x.abs();
+            let ch = &mut self.stats.channels[c];
+            ch.peak = ch.peak.max(x);
+            if x >= self.threshold {
+                ch.near_full_scale += 1;
+                self.run_start[c].get_or_insert(self.position);
+            } else {
+                self.end_run(c, self.position);
+            }
+            if c + 1 == self.channels {
+                self.position += 1;
+            }
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        for c in 0..self.channels {
+            self.end_run(c, self.position);
+        }
+        self.result = Some(self.stats.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeders::FrameType;
+
+    #[test]
+    fn out_of_order_regions_are_merged() {
+        let mut regions = Vec::new();
+        add_region(&mut regions, 5.0, 6.0);
+        add_region(&mut regions, 1.0, 2.0);
+        add_region(&mut regions, 3.0, 4.0);
+        assert_eq!(regions, [(1.0, 2.0), (3.0, 4.0), (5.0, 6.0)]);
+        // bridges all three
+        add_region(&mut regions, 1.5, 5.5);
+        assert_eq!(regions, [(1.0, 6.0)]);
+    }
+
+    #[test]
+    fn only_first_regions_are_kept() {
+        let mut regions = Vec::new();
+        for i in (0..MAX_REGIONS + 10).rev() {
+            add_region(&mut regions, i as f64, i as f64 + 0.5);
+        }
+        assert_eq!(regions.len(), MAX_REGIONS);
+        assert_eq!(regions[0], (0.0, 0.5));
+        assert_eq!(regions[MAX_REGIONS - 1].0, (MAX_REGIONS - 1) as f64);
+    }
+
+    #[test]
+    fn two_channels() {
+        let mut clipping = Clipping::new(&AudioInfo::for_test(10, 2, "pcm_s16le"));
+        // left clips at 2..8, right at 3..6 (ends first) and 9..12
+        let samples: Vec<i16> = (0..14)
+            .flat_map(|i| {
+                let left = if (2..8).contains(&i) { i16::MAX } else { 0 };
+                let right = if (3..6).contains(&i) || (9..12).contains(&i) {
+                    i16::MIN
+                } else {
+                    0
+                };
+                [left, right]
+            })
+            .collect();
+        clipping
+            .feed(&Frame::I16(FrameType::Packed(&samples)))
+            .unwrap();
+        clipping.finalize().unwrap();
+        let stats = clipping.result.unwrap();
+        assert_eq!(stats.regions, [(0.2, 0.8), (0.9, 1.2)]);
+        assert_eq!(stats.runs(), 3);
+        assert_eq!(stats.channels[0].longest_run, 6);
+        assert_eq!(stats.channels[1].longest_run, 3);
+        assert!(stats.is_clipped());
+    }
+}
@@ -0,0 +1,112 @@
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+/// Converts normalized level to dB
+fn db(x: f64) -> f64 {
+    20.0 * x.log10()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LevelStats {
+    /// Sample peak of every channel (normalized, 1.0 is full scale)
+    pub peak: Vec<f64>,
+    /// RMS of every channel (normalized, full scale sine is 1.0 like in DR meter)
+    pub rms: Vec<f64>,
+}
+
+impl LevelStats {
+    /// Highest peak of all channels in dBFS
+    pub fn peak_db(&self) -> f64 {
+        db(self.peak.iter().copied().fold(0.0, f64::max))
+    }
+
+    /// RMS of all channels in dB (mean power, so silent channel does not give -inf)
+    pub fn rms_db(&self) -> f64 {
+        let power = self.rms.iter().map(|x| x * x).sum::<f64>() / self.rms.len().max(1) as f64;
+        db(power.sqrt())
+    }
+}
+
+/// Peak and RMS of whole track (as in foo_dr logs)
+pub struct Levels {
+    channels: usize,
+    n: u64,
+    peak: Vec<f64>,
+    sum: Vec<f64>,
+    /// Available after seeding
+    pub result: Option<LevelStats>,
+}
+
+impl Levels {
+    pub fn new(info: &AudioInfo) -> Self {
+        let channels = info.channels as usize;
+        Self {
+            channels,
+            n: 0,
+            peak: vec![0.0; channels],
+            sum: vec![0.0; channels],
+            result: None,
+        }
+    }
+}
+
+impl Analyzer for Levels {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        for_each_sample(frame, self.channels, |c, x| {
+            self.peak[c] = self.peak[c].max(x.abs());
+            self.sum[c] += x * x;
+            if c + 1 == self.channels {
+                self.n += 1;
+            }
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        let n = self.n.max(1) as f64;
+        self.result = Some(LevelStats {
+            peak: self.peak.clone(),
+            // +3 dB, so full scale sine is 0 dB
+            rms: self.sum.iter().map(|s| (2.0 * s / n).sqrt()).collect(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeders::FrameType;
+
+    #[test]
+    fn rms_is_mean_power() {
+        let stats = LevelStats {
+            peak: vec![0.5, 0.25],
+            rms: vec![0.5, 0.0],
+        };
+        assert!((stats.peak_db() - db(0.5)).abs() < 1e-9);
+        // half of power of left channel
+        assert!((stats.rms_db() - (db(0.5) - 10.0 * 2f64.log10())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn full_scale_sine_is_0_db() {
+        let mut levels = Levels::new(&AudioInfo::for_test(48000, 2, "pcm_f64le"));
+        let samples: Vec<f64> = (0..48000)
+            .flat_map(|i| {
+                let x = (2.0 * std::f64::consts::PI * 1000.0 * f64::from(i) / 48000.0).sin();
+                [x, x / 2.0]
+            })
+            .collect();
+        levels
+            .feed(&Frame::F64(FrameType::Packed(&samples)))
+            .unwrap();
+        levels.finalize().unwrap();
+        let stats = levels.result.unwrap();
+        assert!(stats.rms[0] > 0.9999 && stats.rms[0] < 1.0001);
+        assert!(stats.peak_db().abs() < 1e-6);
+        // mean of 1.0 and 0.25 power
+        assert!((stats.rms_db() - db(0.625f64.sqrt())).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::Write;
+
+use ebur128::{EbuR128, Mode};
+
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+/// Default sampling interval in seconds
+pub const CURVE_INTERVAL: f64 = 0.1;
+
+/// Loudness at one point of time
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessPoint {
+    /// Seconds from start
+    pub time: f64,
+    /// Momentary loudness (400 ms window) in LUFS
+    pub momentary: f64,
+    /// Short-term loudness (3 s window) in LUFS
+    pub short_term: f64,
+}
+
+/// Samples momentary and short-term loudness at fixed interval
+/// (for visualizing loudness over track).
+pub struct LoudnessCurve {
+    rate: u32,
+    channels: usize,
+    ebur: EbuR128,
+    /// in samples (per channel)
+    interval: u64,
+    /// instants fed so far
+    position: u64,
+    /// position of next point
+    next: u64,
+    /// interleaved frame
+    buffer: Vec<f64>,
+    /// Sampled curve (filled while seeding)
+    pub points: Vec<LoudnessPoint>,
+}
+
+impl LoudnessCurve {
+    /// `interval` is in seconds (see [CURVE_INTERVAL])
+    pub fn new(info: &AudioInfo, interval: f64) -> Result<Self, SeedError> {
+        let interval = ((interval * f64::from(info.rate)) as u64).max(1);
+        Ok(Self {
+            rate: info.rate,
+            channels: info.channels as usize,
+            ebur: EbuR128::new(info.channels, info.rate, Mode::M | Mode::S)?,
+            interval,
+            position: 0,
+            next: interval,
+            buffer: Vec::new(),
+            points: Vec::new(),
+        })
+    }
+
+    /// Writes `time,momentary,short_term` CSV (silence is `-inf`)
+    pub fn write_csv<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, "time,momentary,short_term")?;
+        for p in &self.points {
+            writeln!(w, "{:.3},{:.2},{:.2}", p.time, p.momentary, p.short_term)?;
+        }
+        Ok(())
+    }
+
+    /// Writes JSON with array of points (silence is `null`)
+    pub fn write_json<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        let num = |x: f64| {
+            if x.is_finite() {
+                format!("{x:.2}")
+            } else {
+                "null".to_owned()
+            }
+        };
+        write!(
+            w,
+            "{{\"interval\":{:.3},\"points\":[",
+            self.interval as f64 / f64::from(self.rate)
+        )?;
+        for (i, p) in self.points.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "{{\"time\":{:.3},\"momentary\":{},\"short_term\":{}}}",
+                p.time,
+                num(p.momentary),
+                num(p.short_term)
+            )?;
+        }
+        writeln!(w, "]}}")
+    }
+}
+
+impl Analyzer for LoudnessCurve {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        self.buffer.clear();
+        for_each_sample(frame, self.channels, |_, x| self.buffer.push(x));
+        // partial instant (planes not matching channels) can not be fed
+        let whole = self.buffer.len() / self.channels * self.channels;
+        // split frame at points, so they are exact
+        let mut rest = &self.buffer[..whole];
+        while !rest.is_empty() {
+            let take = ((rest.len() / self.channels) as u64).min(self.next - self.position);
+            let (now, later) = rest.split_at(take as usize * self.channels);
+            self.ebur.add_frames_f64(now)?;
+            self.position += take;
+            rest = later;
+            if self.position == self.next {
+                self.points.push(LoudnessPoint {
+                    time: self.position as f64 / f64::from(self.rate),
+                    momentary: self.ebur.loudness_momentary()?,
+                    short_term: self.ebur.loudness_shortterm()?,
+                });
+                self.next += self.interval;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeders::FrameType;
+
+    #[test]
+    fn points_are_exact() {
+        let mut curve =
+            LoudnessCurve::new(&AudioInfo::for_test(48000, 2, "pcm_f32le"), 0.1).unwrap();
+        let samples = vec![0.1f32; 2 * 1000];
+        // frames do not align with interval
+        for _ in 0..48 {
+            curve
+                .feed(&Frame::F32(FrameType::Packed(&samples)))
+                .unwrap();
+        }
+        assert_eq!(curve.points.len(), 10);
+        assert_eq!(curve.points[0].time, 0.1);
+        assert!(curve.points[9].momentary.is_finite());
+    }
+
+    #[test]
+    fn partial_instant_does_not_hang() {
+        let mut curve =
+            LoudnessCurve::new(&AudioInfo::for_test(48000, 2, "pcm_f32le"), 0.01).unwrap();
+        // three planes of stereo file
+        let plane = [0.1f32];
+        curve
+            .feed(&Frame::F32(FrameType::Planar(&[&plane, &plane, &plane])))
+            .unwrap();
+        let samples = vec![0.1f32; 2 * 48000];
+        curve
+            .feed(&Frame::F32(FrameType::Packed(&samples)))
+            .unwrap();
+        assert_eq!(curve.points.len(), 100);
+    }
+}
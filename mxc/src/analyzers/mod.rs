@@ -0,0 +1,183 @@
+use std::any::Any;
+
+use drmeter::DRMeter;
+use ebur128::EbuR128;
+
+use crate::error::SeedError;
+use crate::seeders::{Frame, FrameType};
+
+mod clipping;
+mod levels;
+mod loudness_curve;
+mod resolution;
+mod silence;
+mod spectrogram;
+mod spectrum;
+mod stereo;
+mod waveform;
+pub use clipping::*;
+pub use levels::*;
+pub use loudness_curve::*;
+pub use resolution::*;
+pub use silence::*;
+pub use spectrogram::*;
+pub use spectrum::*;
+pub use stereo::*;
+pub use waveform::*;
+
+/// Helper trait that allows getting concrete [Analyzer] back from `dyn Analyzer`.
+///
+/// It is implemented for every `'static` type, so there is no need to implement it by hand.
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Consumer of decoded samples.
+///
+/// Analyzers are fed frame by frame while [crate::AudioFile] is being seeded
+/// (so whole file is never stored in memory) and are finalized after last frame.
+pub trait Analyzer: AsAny + Send {
+    /// Consume one decoded frame
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError>;
+
+    /// Called once after all frames were fed
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        Ok(())
+    }
+}
+
+/// Sample type that decoders produce
+pub trait Sample: Copy {
+    /// Normalized to [-1.0, 1.0]
+    fn to_f64(self) -> f64;
+}
+
+impl Sample for i16 {
+    fn to_f64(self) -> f64 {
+        f64::from(self) / 32768.0
+    }
+}
+
+impl Sample for i32 {
+    fn to_f64(self) -> f64 {
+        f64::from(self) / 2147483648.0
+    }
+}
+
+impl Sample for f32 {
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl Sample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Calls `f(channel, sample)` for every sample of frame in interleaved order
+/// (so all channels of one instant come one after another), normalized to [-1.0, 1.0].
+pub fn for_each_sample(frame: &Frame, channels: usize, mut f: impl FnMut(usize, f64)) {
+    fn inner<T: Sample>(frame: &FrameType<T>, channels: usize, f: &mut impl FnMut(usize, f64)) {
+        match frame {
+            FrameType::Packed(x) => {
+                for samples in x.chunks_exact(channels) {
+                    for (c, s) in samples.iter().enumerate() {
+                        f(c, s.to_f64())
+                    }
+                }
+            }
+            FrameType::Planar(planes) => {
+                let len = planes.first().map_or(0, |p| p.len());
+                for i in 0..len {
+                    for (c, p) in planes.iter().enumerate() {
+                        f(c, p[i].to_f64())
+                    }
+                }
+            }
+        }
+    }
+    match frame {
+        Frame::I16(x) => inner(x, channels, &mut f),
+        Frame::I32(x) => inner(x, channels, &mut f),
+        Frame::F32(x) => inner(x, channels, &mut f),
+        Frame::F64(x) => inner(x, channels, &mut f),
+    }
+}
+
+/// Dispatches [Frame] to `add_frames_*` family of functions
+/// (libebur128 and DR meter share the same naming)
+macro_rules! add_frames {
+    ($s:expr, $frame:expr) => {
+        match $frame {
+            Frame::I16(FrameType::Packed(x)) => $s.add_frames_i16(x)?,
+            Frame::I32(FrameType::Packed(x)) => $s.add_frames_i32(x)?,
+            Frame::F32(FrameType::Packed(x)) => $s.add_frames_f32(x)?,
+            Frame::F64(FrameType::Packed(x)) => $s.add_frames_f64(x)?,
+            Frame::I16(FrameType::Planar(x)) => $s.add_frames_planar_i16(x)?,
+            Frame::I32(FrameType::Planar(x)) => $s.add_frames_planar_i32(x)?,
+            Frame::F32(FrameType::Planar(x)) => $s.add_frames_planar_f32(x)?,
+            Frame::F64(FrameType::Planar(x)) => $s.add_frames_planar_f64(x)?,
+        }
+    };
+}
+
+impl Analyzer for EbuR128 {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        add_frames!(self, frame);
+        Ok(())
+    }
+}
+
+impl Analyzer for DRMeter {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        add_frames!(self, frame);
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        DRMeter::finalize(self)?;
+        Ok(())
+    }
+}
+
+/// Synthetic signals for analyzer tests
+#[cfg(test)]
+pub(crate) mod test_signals {
+    use super::FFT_SIZE;
+
+    /// Mono sum of sines that sit exactly on every second [FFT_SIZE] bin from `first` to `last`
+    /// (so Hann window does not leak them outside of neighbouring bins)
+    /// on top of faint white noise (like dither, -100 dBFS)
+    pub(crate) fn bin_sines(first: usize, last: usize, len: usize) -> Vec<f32> {
+        let mut seed = 1u32;
+        (0..len)
+            .map(|i| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let noise = 1e-5 * (f64::from(seed) / f64::from(u32::MAX) * 2.0 - 1.0);
+                (first..=last)
+                    .step_by(2)
+                    .map(|k| {
+                        // pseudo random phase, so sines do not add up into one peak
+                        let phase = (k * k) as f64 * 0.7;
+                        let x = 2.0 * std::f64::consts::PI * (k * i) as f64 / FFT_SIZE as f64;
+                        0.001 * (x + phase).sin()
+                    })
+                    .sum::<f64>() as f32
+                    + noise as f32
+            })
+            .collect()
+    }
+}
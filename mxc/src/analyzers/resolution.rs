@@ -0,0 +1,225 @@
+use std::fmt::Display;
+
+use super::{Analyzer, SpectralSummary, Spectrum};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame, FrameType};
+
+/// Sample rates that content is commonly upsampled from
+const SOURCE_RATES: [u32; 4] = [44100, 48000, 88200, 96000];
+
+/// Calls `f` for every sample (order does not matter)
+fn for_each_raw<T: Copy>(frame: &FrameType<T>, mut f: impl FnMut(T)) {
+    match frame {
+        FrameType::Packed(x) => x.iter().copied().for_each(f),
+        FrameType::Planar(planes) => {
+            for p in planes.iter() {
+                p.iter().copied().for_each(&mut f)
+            }
+        }
+    }
+}
+
+/// Resolution that file really has
+#[derive(Debug, Clone, Copy)]
+pub struct TrueResolution {
+    /// Bits that are actually used (None if samples are not integers, like decoded lossy)
+    pub bits: Option<u32>,
+    /// Bits per sample as declared by file
+    pub declared_bits: Option<u32>,
+    pub rate: u32,
+    /// Sample rate that content was likely upsampled from
+    pub source_rate: Option<u32>,
+}
+
+impl TrueResolution {
+    /// True if file claims more than it has
+    pub fn is_padded_or_upsampled(&self) -> bool {
+        let padded = match (self.bits, self.declared_bits) {
+            (Some(bits), Some(declared)) => bits < declared,
+            _ => false,
+        };
+        padded || self.source_rate.is_some()
+    }
+}
+
+impl Display for TrueResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.bits {
+            Some(bits) => write!(f, "{bits} bit")?,
+            None => write!(f, "float")?,
+        }
+        if let Some(declared) = self.declared_bits {
+            write!(f, " (declared {declared} bit)")?;
+        }
+        write!(f, ", {} Hz", self.rate)?;
+        if let Some(source) = self.source_rate {
+            write!(f, " (upsampled from {source} Hz)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Detects padded bit depth (unused least significant bits)
+/// and band-limited spectrum that is consistent with upsampling.
+///
+/// It runs [Spectrum] internally (see [Resolution::spectrum]),
+/// so there is no need to register both.
+pub struct Resolution {
+    declared_bits: Option<u32>,
+    rate: u32,
+    /// bits of container (type) that samples came in
+    container_bits: u32,
+    /// all samples OR-ed together (trailing zeros are unused bits)
+    or: u64,
+    /// some float samples are not integers even at 24 bits
+    non_integer: bool,
+    spectrum: Spectrum,
+    /// Available after seeding
+    pub result: Option<TrueResolution>,
+}
+
+impl Resolution {
+    pub fn new(info: &AudioInfo) -> Self {
+        Self {
+            declared_bits: info.bits_per_raw_sample,
+            rate: info.rate,
+            container_bits: 0,
+            or: 0,
+            non_integer: false,
+            spectrum: Spectrum::new(info),
+            result: None,
+        }
+    }
+
+    /// Spectral summary that upsampling detection is based on (available after seeding)
+    pub fn spectrum(&self) -> Option<&SpectralSummary> {
+        self.spectrum.summary.as_ref()
+    }
+
+    fn ints<T: Copy + Into<i64>>(&mut self, frame: &FrameType<T>, bits: u32) {
+        self.container_bits = self.container_bits.max(bits);
+        let mut or = 0;
+        for_each_raw(frame, |x| or |= x.into() as u64);
+        self.or |= or;
+    }
+
+    fn floats<T: Copy + Into<f64>>(&mut self, frame: &FrameType<T>) {
+        // float is integer PCM with at most 24 bits if this is integer
+        const SCALE: f64 = (1 << 23) as f64;
+        self.container_bits = self.container_bits.max(24);
+        let mut or = 0;
+        let mut non_integer = false;
+        for_each_raw(frame, |x| {
+            let m = x.into() * SCALE;
+            if m.fract() != 0.0 {
+                non_integer = true;
+            } else {
+                or |= m as i64 as u64;
+            }
+        });
+        self.or |= or;
+        self.non_integer |= non_integer;
+    }
+
+    /// Smallest common rate whose Nyquist frequency explains brick wall cutoff
+    fn source_rate(&self) -> Option<u32> {
+        let summary = self.spectrum.summary.as_ref()?;
+        let nyquist = f64::from(self.rate) / 2.0;
+        if summary.cutoff > nyquist * 0.9 || summary.steepness < 20.0 {
+            return None;
+        }
+        SOURCE_RATES
+            .iter()
+            .copied()
+            .filter(|&r| r < self.rate)
+            .find(|&r| summary.cutoff <= f64::from(r) / 2.0 + 500.0)
+    }
+}
+
+impl Analyzer for Resolution {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        match frame {
+            Frame::I16(x) => self.ints(x, 16),
+            Frame::I32(x) => self.ints(x, 32),
+            Frame::F32(x) => self.floats(x),
+            Frame::F64(x) => self.floats(x),
+        }
+        self.spectrum.feed(frame)
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        self.spectrum.finalize()?;
+        let bits = if self.non_integer || self.or == 0 {
+            None
+        } else {
+            Some(self.container_bits - self.or.trailing_zeros().min(self.container_bits))
+        };
+        self.result = Some(TrueResolution {
+            bits,
+            declared_bits: self.declared_bits,
+            rate: self.rate,
+            source_rate: self.source_rate(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzers::test_signals::bin_sines;
+    use crate::analyzers::FFT_SIZE;
+
+    fn resolution(info: &AudioInfo, frame: &Frame) -> TrueResolution {
+        let mut resolution = Resolution::new(info);
+        resolution.feed(frame).unwrap();
+        resolution.finalize().unwrap();
+        resolution.result.unwrap()
+    }
+
+    /// Band limited 96 kHz signal
+    fn upsampled(last_hz: f64) -> TrueResolution {
+        let samples = bin_sines(
+            2,
+            (last_hz * FFT_SIZE as f64 / 96000.0) as usize,
+            4 * FFT_SIZE,
+        );
+        resolution(
+            &AudioInfo::for_test(96000, 1, "flac"),
+            &Frame::F32(FrameType::Packed(&samples)),
+        )
+    }
+
+    #[test]
+    fn upsampled_from_44100() {
+        let result = upsampled(20000.0);
+        assert_eq!(result.source_rate, Some(44100));
+        assert!(result.is_padded_or_upsampled());
+    }
+
+    #[test]
+    fn full_band_is_not_upsampled() {
+        assert_eq!(upsampled(44000.0).source_rate, None);
+    }
+
+    #[test]
+    fn padded_bits() {
+        let mut info = AudioInfo::for_test(44100, 1, "pcm_s16le");
+        info.bits_per_raw_sample = Some(16);
+        // only upper 8 bits are used
+        let samples: Vec<i16> = (-100..100).map(|x| x << 8).collect();
+        let result = resolution(&info, &Frame::I16(FrameType::Packed(&samples)));
+        assert_eq!(result.bits, Some(8));
+        assert!(result.is_padded_or_upsampled());
+    }
+
+    #[test]
+    fn full_bits() {
+        let mut info = AudioInfo::for_test(44100, 1, "pcm_s16le");
+        info.bits_per_raw_sample = Some(16);
+        let samples: Vec<i16> = (-100..100).map(|x| x * 3).collect();
+        let result = resolution(&info, &Frame::I16(FrameType::Packed(&samples)));
+        assert_eq!(result.bits, Some(16));
+        assert!(!result.is_padded_or_upsampled());
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::Display;
+
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+/// Default silence threshold in dBFS
+pub const SILENCE_THRESHOLD: f64 = -60.0;
+
+/// Internal silence shorter than this (in seconds) is not considered a gap
+pub const MIN_GAP: f64 = 2.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct SilenceStats {
+    /// Silence at the start in seconds
+    pub leading: f64,
+    /// Silence at the end in seconds
+    pub trailing: f64,
+    /// Internal silent gaps as (start, end) in seconds
+    pub gaps: Vec<(f64, f64)>,
+    /// Whole file is below threshold
+    pub silent: bool,
+    /// Every sample is exactly zero
+    pub digital_silence: bool,
+}
+
+impl SilenceStats {
+    /// Silence between end of this track and start of `next` one (in seconds)
+    pub fn gap_to(&self, next: &SilenceStats) -> f64 {
+        self.trailing + next.leading
+    }
+}
+
+impl Display for SilenceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.digital_silence {
+            return write!(f, "digital silence");
+        }
+        if self.silent {
+            return write!(f, "silent");
+        }
+        write!(
+            f,
+            "leading {:.2}s, trailing {:.2}s",
+            self.leading, self.trailing
+        )?;
+        for (i, (start, end)) in self.gaps.iter().enumerate() {
+            let sep = if i == 0 { ", gaps at" } else { "," };
+            write!(f, "{sep} {start:.2}s-{end:.2}s")?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds leading/trailing silence, internal gaps and digital-silent files
+/// (hidden tracks, broken rips, pregap issues).
+pub struct Silence {
+    rate: u32,
+    channels: usize,
+    /// linear
+    threshold: f64,
+    /// in samples
+    min_gap: u64,
+    /// instants (samples per channel) seen so far
+    position: u64,
+    /// current instant is above threshold
+    loud: bool,
+    first_loud: Option<u64>,
+    last_loud: Option<u64>,
+    /// start of current silence (after first loud instant)
+    silence_start: Option<u64>,
+    gaps: Vec<(u64, u64)>,
+    nonzero: bool,
+    /// Available after seeding
+    pub result: Option<SilenceStats>,
+}
+
+impl Silence {
+    pub fn new(info: &AudioInfo) -> Self {
+        Self {
+            rate: info.rate,
+            channels: info.channels as usize,
+            threshold: 0.0,
+            min_gap: 0,
+            position: 0,
+            loud: false,
+            first_loud: None,
+            last_loud: None,
+            silence_start: None,
+            gaps: Vec::new(),
+            nonzero: false,
+            result: None,
+        }
+        .with_threshold(SILENCE_THRESHOLD)
+        .with_min_gap(MIN_GAP)
+    }
+
+    /// Silence threshold in dBFS
+    pub fn with_threshold(mut self, dbfs: f64) -> Self {
+        self.threshold = 10f64.powf(dbfs / 20.0);
+        self
+    }
+
+    /// Minimal duration of internal gap in seconds
+    pub fn with_min_gap(mut self, seconds: f64) -> Self {
+        self.min_gap = (seconds * f64::from(self.rate)) as u64;
+        self
+    }
+
+    fn instant(&mut self) {
+        let pos = self.position;
+        if self.loud {
+            if self.first_loud.is_none() {
+                self.first_loud = Some(pos);
+            }
+            if let Some(start) = self.silence_start.take() {
+                if pos - start >= self.min_gap {
+                    self.gaps.push((start, pos));
+                }
+            }
+            self.last_loud = Some(pos);
+        } else if self.first_loud.is_some() && self.silence_start.is_none() {
+            self.silence_start = Some(pos);
+        }
+        self.loud = false;
+        self.position += 1;
+    }
+}
+
+impl Analyzer for Silence {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        for_each_sample(frame, self.channels, |c, x| {
+            self.nonzero |= x != 0.0;
+            self.loud |= x.abs() > self.threshold;
+            if c + 1 == self.channels {
+                self.instant();
+            }
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        let rate = f64::from(self.rate);
+        let seconds = |samples: u64| samples as f64 / rate;
+        self.result = Some(match (self.first_loud, self.last_loud) {
+            (Some(first), Some(last)) => SilenceStats {
+                leading: seconds(first),
+                trailing: seconds(self.position - last - 1),
+                gaps: self
+                    .gaps
+                    .iter()
+                    .map(|&(start, end)| (seconds(start), seconds(end)))
+                    .collect(),
+                silent: false,
+                digital_silence: false,
+            },
+            _ => SilenceStats {
+                leading: seconds(self.position),
+                trailing: 0.0,
+                gaps: Vec::new(),
+                silent: true,
+                digital_silence: !self.nonzero,
+            },
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeders::FrameType;
+
+    fn silence(samples: &[f32]) -> SilenceStats {
+        let mut silence = Silence::new(&AudioInfo::for_test(10, 1, "pcm_f32le"));
+        silence
+            .feed(&Frame::F32(FrameType::Packed(samples)))
+            .unwrap();
+        silence.finalize().unwrap();
+        silence.result.unwrap()
+    }
+
+    /// `n` samples of level `x`
+    fn run(x: f32, n: usize) -> impl Iterator<Item = f32> {
+        std::iter::repeat_n(x, n)
+    }
+
+    #[test]
+    fn leading_trailing_and_gaps() {
+        let samples: Vec<f32> = run(0.0, 5)
+            .chain(run(0.5, 10))
+            // -80 dBFS, long enough to be a gap
+            .chain(run(1e-4, 25))
+            .chain(run(-0.5, 10))
+            // too short to be a gap
+            .chain(run(0.0, 15))
+            .chain(run(0.5, 1))
+            .chain(run(0.0, 3))
+            .collect();
+        let stats = silence(&samples);
+        assert_eq!(stats.leading, 0.5);
+        assert_eq!(stats.trailing, 0.3);
+        assert_eq!(stats.gaps, [(1.5, 4.0)]);
+        assert!(!stats.silent && !stats.digital_silence);
+        assert_eq!(
+            stats.to_string(),
+            "leading 0.50s, trailing 0.30s, gaps at 1.50s-4.00s"
+        );
+        assert_eq!(stats.gap_to(&stats), 0.8);
+    }
+
+    #[test]
+    fn silent_files() {
+        let quiet = silence(&[1e-4; 30]);
+        assert!(quiet.silent && !quiet.digital_silence);
+        assert_eq!(quiet.leading, 3.0);
+        let zero = silence(&[0.0; 30]);
+        assert!(zero.silent && zero.digital_silence);
+        assert_eq!(zero.to_string(), "digital silence");
+    }
+}
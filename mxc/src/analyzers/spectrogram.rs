@@ -0,0 +1,288 @@
+use std::io::Write;
+
+use super::{Analyzer, Stft, FFT_SIZE};
+use crate::error::SeedError;
+use crate::options::FrequencyScale;
+use crate::seeders::{AudioInfo, Frame};
+
+/// Default image width in pixels
+pub const SPECTROGRAM_WIDTH: u32 = 1200;
+
+/// Default image height in pixels
+pub const SPECTROGRAM_HEIGHT: u32 = 600;
+
+/// Levels below this (dBFS) are black
+const FLOOR: f32 = -120.0;
+
+/// Lowest frequency of logarithmic scale
+const LOG_LOW: f64 = 20.0;
+
+/// Maps level (0.0 is floor, 1.0 is full scale) to color (black, blue, red, yellow, white)
+fn color(x: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.1, 0.0, 0.5],
+        [0.8, 0.0, 0.3],
+        [1.0, 0.8, 0.0],
+        [1.0, 1.0, 1.0],
+    ];
+    let x = x.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let t = x - i as f32;
+    let mut rgb = [0; 3];
+    for (c, v) in rgb.iter_mut().enumerate() {
+        *v = ((STOPS[i][c] * (1.0 - t) + STOPS[i + 1][c] * t) * 255.0) as u8;
+    }
+    rgb
+}
+
+/// Averages levels of FFT windows into image columns
+#[derive(Default)]
+struct Columns {
+    width: usize,
+    /// windows averaged into one column
+    per_column: usize,
+    /// sum of levels of current column
+    sum: Vec<f32>,
+    /// windows in current column
+    count: usize,
+    /// levels (dBFS) of rows, at most `2 * width` columns
+    levels: Vec<Vec<f32>>,
+}
+
+impl Columns {
+    /// `windows` is expected number of FFT windows (if length is known)
+    fn new(width: usize, height: usize, windows: Option<f64>) -> Self {
+        let per_column = windows.map_or(1.0, |n| (n / width as f64).ceil());
+        Self {
+            width,
+            per_column: (per_column as usize).max(1),
+            sum: vec![0.0; height],
+            count: 0,
+            levels: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, levels: impl Iterator<Item = f32>) {
+        for (sum, level) in self.sum.iter_mut().zip(levels) {
+            *sum += level;
+        }
+        self.count += 1;
+        if self.count == self.per_column {
+            self.end_column();
+        }
+    }
+
+    fn end_column(&mut self) {
+        let n = self.count as f32;
+        self.levels.push(self.sum.iter().map(|s| s / n).collect());
+        self.sum.fill(0.0);
+        self.count = 0;
+        // length was unknown (or wrong), so merge pairs of columns to keep memory bounded
+        if self.levels.len() == 2 * self.width {
+            self.levels = self
+                .levels
+                .chunks(2)
+                .map(|pair| {
+                    pair[0]
+                        .iter()
+                        .zip(&pair[1])
+                        .map(|(a, b)| (a + b) / 2.0)
+                        .collect()
+                })
+                .collect();
+            self.per_column *= 2;
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.count > 0 {
+            self.end_column();
+        }
+    }
+}
+
+/// Renders spectrogram of (downmixed) audio as PNG image (QA of suspicious files)
+pub struct Spectrogram {
+    rate: u32,
+    channels: usize,
+    /// in seconds (if known)
+    duration: Option<f64>,
+    fft_size: usize,
+    scale: FrequencyScale,
+    width: u32,
+    height: u32,
+    /// None until first frame (so builder methods can change FFT size)
+    stft: Option<Stft>,
+    /// bins `[start, end)` that make every row (bottom first)
+    rows: Vec<(usize, usize)>,
+    /// averaged while seeding, so memory does not grow with length
+    columns: Columns,
+}
+
+impl Spectrogram {
+    pub fn new(info: &AudioInfo) -> Self {
+        Self {
+            rate: info.rate,
+            channels: info.channels as usize,
+            duration: info.duration,
+            fft_size: FFT_SIZE,
+            scale: FrequencyScale::default(),
+            width: SPECTROGRAM_WIDTH,
+            height: SPECTROGRAM_HEIGHT,
+            stft: None,
+            rows: Vec::new(),
+            columns: Columns::default(),
+        }
+    }
+
+    /// FFT size (rounded to power of two, at least 64)
+    pub fn with_fft_size(mut self, size: usize) -> Self {
+        self.fft_size = size.max(64).next_power_of_two();
+        self
+    }
+
+    pub fn with_scale(mut self, scale: FrequencyScale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Image dimensions in pixels
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        self
+    }
+
+    /// Bins of every row for current scale
+    fn rows(&self) -> Vec<(usize, usize)> {
+        let bins = self.fft_size / 2 + 1;
+        let nyquist = f64::from(self.rate) / 2.0;
+        let hz_to_bin = |hz: f64| ((hz / nyquist * (bins - 1) as f64) as usize).min(bins - 1);
+        let edge = |r: u32| {
+            let x = f64::from(r) / f64::from(self.height);
+            match self.scale {
+                FrequencyScale::Linear => x * nyquist,
+                FrequencyScale::Log => LOG_LOW * (nyquist / LOG_LOW).powf(x),
+            }
+        };
+        (0..self.height)
+            .map(|r| {
+                let start = hz_to_bin(edge(r));
+                // every row has at least one bin
+                (start, hz_to_bin(edge(r + 1)).max(start + 1).min(bins))
+            })
+            .collect()
+    }
+
+    /// Writes spectrogram as RGB PNG image
+    pub fn write_png<W: Write>(&self, w: W) -> std::io::Result<()> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut data = vec![0; width * height * 3];
+        if !self.columns.levels.is_empty() {
+            let n = self.columns.levels.len();
+            for x in 0..width {
+                // average all columns of pixel (or nearest one if there are not enough)
+                let start = (x * n / width).min(n - 1);
+                let end = ((x + 1) * n / width).clamp(start + 1, n);
+                let columns = &self.columns.levels[start..end];
+                for y in 0..height {
+                    let level = columns.iter().map(|c| c[y]).sum::<f32>() / columns.len() as f32;
+                    // top row is highest frequency
+                    let i = ((height - 1 - y) * width + x) * 3;
+                    data[i..i + 3].copy_from_slice(&color(1.0 - level / FLOOR));
+                }
+            }
+        }
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+}
+
+impl Analyzer for Spectrogram {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        if self.stft.is_none() {
+            self.stft = Some(Stft::new(self.fft_size, self.channels));
+            self.rows = self.rows();
+            let windows = self
+                .duration
+                .map(|d| d * f64::from(self.rate) / self.fft_size as f64);
+            self.columns = Columns::new(self.width as usize, self.height as usize, windows);
+        }
+        // full scale sine is 0 dB (Hann window halves amplitude)
+        let reference = (self.fft_size as f32 / 4.0).powi(2);
+        let (rows, columns) = (&self.rows, &mut self.columns);
+        self.stft.as_mut().unwrap().feed(frame, |bins| {
+            columns.add(rows.iter().map(|&(start, end)| {
+                let power = bins[start..end]
+                    .iter()
+                    .map(|b| b.norm_sqr())
+                    .fold(0.0, f32::max);
+                (10.0 * (power / reference + 1e-20).log10()).max(FLOOR)
+            }))
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        self.columns.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzers::test_signals::bin_sines;
+    use crate::seeders::FrameType;
+
+    fn windows(columns: &mut Columns, n: usize) {
+        for i in 0..n {
+            columns.add(std::iter::once(i as f32));
+        }
+        columns.finish();
+    }
+
+    #[test]
+    fn known_length_fills_width() {
+        let mut columns = Columns::new(4, 1, Some(8.0));
+        windows(&mut columns, 8);
+        assert_eq!(columns.levels, [[0.5], [2.5], [4.5], [6.5]]);
+    }
+
+    #[test]
+    fn unknown_length_is_bounded() {
+        let mut columns = Columns::new(2, 1, None);
+        windows(&mut columns, 10);
+        // 0..4, 4..8 and 8..10
+        assert_eq!(columns.levels, [[1.5], [5.5], [8.5]]);
+    }
+
+    #[test]
+    fn one_column_per_pixel() {
+        let rate = 48000;
+        let samples = bin_sines(10, 500, 64 * FFT_SIZE);
+        let mut info = AudioInfo::for_test(rate, 1, "pcm_f32le");
+        info.duration = Some(samples.len() as f64 / f64::from(rate));
+        let mut spectrogram = Spectrogram::new(&info).with_size(8, 16);
+        // frames do not align with windows
+        for chunk in samples.chunks(1000) {
+            spectrogram
+                .feed(&Frame::F32(FrameType::Packed(chunk)))
+                .unwrap();
+        }
+        spectrogram.finalize().unwrap();
+        assert_eq!(spectrogram.columns.levels.len(), 8);
+        // sines are at -60 dBFS, highest row is empty
+        let column = &spectrogram.columns.levels[0];
+        assert!(column[0] > -70.0);
+        assert!(column[15] < -100.0);
+        let mut png = Vec::new();
+        spectrogram.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+}
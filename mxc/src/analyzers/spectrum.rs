@@ -0,0 +1,322 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+/// FFT size used for spectral summary (~10 Hz resolution at 44.1 kHz)
+pub const FFT_SIZE: usize = 4096;
+
+/// Codecs that are lossless (everything starting with `pcm_` is also)
+const LOSSLESS_CODECS: [&str; 10] = [
+    "flac",
+    "alac",
+    "wavpack",
+    "ape",
+    "tta",
+    "tak",
+    "mlp",
+    "truehd",
+    "wmalossless",
+    "shorten",
+];
+
+pub(crate) fn is_lossless(codec: &str) -> bool {
+    codec.starts_with("pcm_") || LOSSLESS_CODECS.contains(&codec)
+}
+
+/// Average of `x[lo..hi]` (at least one element)
+fn average(x: &[f64], lo: usize, hi: usize) -> f64 {
+    let hi = hi.clamp(lo + 1, x.len());
+    let lo = lo.min(hi - 1);
+    x[lo..hi].iter().sum::<f64>() / (hi - lo) as f64
+}
+
+/// Short-time Fourier transform of mono downmix (non-overlapping Hann windows)
+pub(crate) struct Stft {
+    channels: usize,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// downmixed samples waiting for FFT
+    input: Vec<f32>,
+    /// sum of channels of current instant
+    mix: f64,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Stft {
+    pub(crate) fn new(size: usize, channels: usize) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        let window = (0..size)
+            .map(|i| {
+                let x = std::f32::consts::PI * i as f32 / size as f32;
+                x.sin().powi(2)
+            })
+            .collect();
+        Self {
+            channels,
+            scratch: vec![Complex::default(); fft.get_inplace_scratch_len()],
+            fft,
+            window,
+            input: Vec::with_capacity(size),
+            mix: 0.0,
+            buffer: vec![Complex::default(); size],
+        }
+    }
+
+    /// Feeds frame and calls `f` with bins `0..=size/2` of every completed window
+    pub(crate) fn feed(&mut self, frame: &Frame, mut f: impl FnMut(&[Complex<f32>])) {
+        for_each_sample(frame, self.channels, |c, x| {
+            self.mix += x;
+            if c + 1 == self.channels {
+                self.input.push((self.mix / self.channels as f64) as f32);
+                self.mix = 0.0;
+                if self.input.len() == self.window.len() {
+                    for ((b, x), w) in self.buffer.iter_mut().zip(&self.input).zip(&self.window) {
+                        *b = Complex::new(x * w, 0.0);
+                    }
+                    self.fft
+                        .process_with_scratch(&mut self.buffer, &mut self.scratch);
+                    self.input.clear();
+                    f(&self.buffer[..=self.window.len() / 2]);
+                }
+            }
+        });
+    }
+}
+
+/// Frequency band with its average level
+#[derive(Debug, Clone, Copy)]
+pub struct Band {
+    pub low: f64,
+    pub high: f64,
+    /// Level in dB relative to reference (200 Hz - 2 kHz)
+    pub level: f64,
+}
+
+/// Average spectrum of whole file boiled down to few numbers
+#[derive(Debug, Clone)]
+pub struct SpectralSummary {
+    /// Frequency (Hz) above which there is (almost) no content
+    pub cutoff: f64,
+    /// Drop (dB) from 500 Hz below to 500 Hz above cutoff.
+    /// Lossy encoders cut like a brick wall.
+    pub steepness: f64,
+    pub bands: Vec<Band>,
+    /// Likelihood (0.0 - 1.0) that lossless file was made from lossy source
+    /// (None for lossy files)
+    pub transcode: Option<f64>,
+}
+
+impl SpectralSummary {
+    pub fn is_likely_transcoded(&self) -> bool {
+        self.transcode.is_some_and(|c| c >= 0.5)
+    }
+}
+
+impl Display for SpectralSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cutoff {:.1} kHz (drop {:.0} dB)",
+            self.cutoff / 1000.0,
+            self.steepness
+        )?;
+        match self.transcode {
+            Some(c) if self.is_likely_transcoded() => {
+                write!(f, ", likely transcoded (confidence {:.0}%)", c * 100.0)
+            }
+            Some(c) => write!(f, ", lossless (transcode confidence {:.0}%)", c * 100.0),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Spectral analyzer that detects lossless files made from lossy ones ("fake lossless").
+///
+/// Lossy encoders low-pass audio (16 kHz for 128 kb/s MP3, ~20 kHz for 320 kb/s),
+/// so such files have a sharp cutoff well below Nyquist frequency.
+pub struct Spectrum {
+    rate: u32,
+    lossless: bool,
+    stft: Stft,
+    /// accumulated power of every bin
+    power: Vec<f64>,
+    windows: usize,
+    /// Available after seeding (None if file is too short or silent)
+    pub summary: Option<SpectralSummary>,
+}
+
+impl Spectrum {
+    pub fn new(info: &AudioInfo) -> Self {
+        Self {
+            rate: info.rate,
+            lossless: is_lossless(&info.codec),
+            stft: Stft::new(FFT_SIZE, info.channels as usize),
+            power: vec![0.0; FFT_SIZE / 2 + 1],
+            windows: 0,
+            summary: None,
+        }
+    }
+
+    fn bin(&self, hz: f64) -> usize {
+        ((hz * FFT_SIZE as f64 / f64::from(self.rate)) as usize).min(self.power.len() - 1)
+    }
+
+    fn summarize(&self) -> Option<SpectralSummary> {
+        if self.windows == 0 {
+            return None;
+        }
+        let db: Vec<f64> = self
+            .power
+            .iter()
+            .map(|p| 10.0 * (p / self.windows as f64 + 1e-20).log10())
+            .collect();
+        // smooth over ~200 Hz, so single tones do not matter
+        let half = self.bin(100.0).max(1);
+        let smooth: Vec<f64> = (0..db.len())
+            .map(|i| {
+                let lo = i.saturating_sub(half);
+                let hi = (i + half + 1).min(db.len());
+                db[lo..hi].iter().sum::<f64>() / (hi - lo) as f64
+            })
+            .collect();
+        let reference = average(&smooth, self.bin(200.0), self.bin(2000.0));
+        // nothing to see here
+        if reference < -120.0 {
+            return None;
+        }
+        let floor = smooth[self.bin(1000.0)..]
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        // highest frequency that is clearly above floor
+        let cutoff_bin = smooth
+            .iter()
+            .rposition(|&x| x > floor + 10.0)
+            .unwrap_or_default();
+        let hz = |bin: usize| bin as f64 * f64::from(self.rate) / FFT_SIZE as f64;
+        let cutoff = hz(cutoff_bin);
+        let steepness = smooth[self.bin(cutoff - 500.0)] - smooth[self.bin(cutoff + 500.0)];
+
+        let nyquist = f64::from(self.rate) / 2.0;
+        let bands = [
+            20.0, 250.0, 2000.0, 8000.0, 12000.0, 16000.0, 19000.0, 20000.0,
+        ]
+        .iter()
+        .copied()
+        .chain(std::iter::once(nyquist))
+        .filter(|&f| f <= nyquist)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|w| Band {
+            low: w[0],
+            high: w[1],
+            level: average(&db, self.bin(w[0]), self.bin(w[1])) - reference,
+        })
+        .collect();
+
+        let transcode = self.lossless.then(|| {
+            // genuine content goes up to anti-aliasing filter
+            let expected = (nyquist * 0.93).min(20500.0);
+            let gap = expected - cutoff;
+            if gap <= 0.0 {
+                0.0
+            } else {
+                let gap_score = (gap / 3000.0).min(1.0);
+                let steep_score = ((steepness - 10.0) / 30.0).clamp(0.0, 1.0);
+                0.5 * gap_score + 0.5 * steep_score
+            }
+        });
+
+        Some(SpectralSummary {
+            cutoff,
+            steepness,
+            bands,
+            transcode,
+        })
+    }
+}
+
+impl Analyzer for Spectrum {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        let power = &mut self.power;
+        let windows = &mut self.windows;
+        self.stft.feed(frame, |bins| {
+            for (p, b) in power.iter_mut().zip(bins) {
+                *p += f64::from(b.norm_sqr());
+            }
+            *windows += 1;
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        self.summary = self.summarize();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzers::test_signals::bin_sines;
+    use crate::seeders::FrameType;
+
+    fn summary(rate: u32, codec: &str, last_bin: usize) -> SpectralSummary {
+        let mut spectrum = Spectrum::new(&AudioInfo::for_test(rate, 1, codec));
+        let samples = bin_sines(2, last_bin, 4 * FFT_SIZE);
+        spectrum
+            .feed(&Frame::F32(FrameType::Packed(&samples)))
+            .unwrap();
+        spectrum.finalize().unwrap();
+        spectrum.summary.unwrap()
+    }
+
+    /// FFT bin of frequency at 44.1 kHz
+    fn bin(hz: f64) -> usize {
+        (hz * FFT_SIZE as f64 / 44100.0) as usize
+    }
+
+    #[test]
+    fn lossless_with_16khz_cutoff_is_transcode() {
+        let summary = summary(44100, "flac", bin(16000.0));
+        assert!(
+            (15500.0..16500.0).contains(&summary.cutoff),
+            "{}",
+            summary.cutoff
+        );
+        assert!(summary.steepness > 40.0, "{}", summary.steepness);
+        assert!(summary.is_likely_transcoded());
+    }
+
+    #[test]
+    fn full_band_lossless_is_not_transcode() {
+        let summary = summary(44100, "flac", bin(21000.0));
+        assert!(summary.cutoff > 20500.0, "{}", summary.cutoff);
+        assert_eq!(summary.transcode, Some(0.0));
+        assert!(!summary.is_likely_transcoded());
+    }
+
+    #[test]
+    fn lossy_has_no_transcode_score() {
+        let summary = summary(44100, "mp3", bin(16000.0));
+        assert_eq!(summary.transcode, None);
+    }
+
+    #[test]
+    fn silence_has_no_summary() {
+        let mut spectrum = Spectrum::new(&AudioInfo::for_test(44100, 1, "flac"));
+        let samples = vec![0.0f32; 4 * FFT_SIZE];
+        spectrum
+            .feed(&Frame::F32(FrameType::Packed(&samples)))
+            .unwrap();
+        spectrum.finalize().unwrap();
+        assert!(spectrum.summary.is_none());
+    }
+}
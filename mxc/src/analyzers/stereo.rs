@@ -0,0 +1,218 @@
+use std::fmt::Display;
+
+use ebur128::{EbuR128, Mode};
+
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+#[derive(Debug, Clone)]
+pub struct StereoStats {
+    /// Correlation of first two channels (1.0 is mono, -1.0 is inverted polarity)
+    pub correlation: f64,
+    /// Integrated loudness of each channel in LUFS
+    pub loudness: Vec<f64>,
+    /// Difference between loudest and quietest channel in LU
+    pub imbalance: f64,
+    /// DC offset of each channel (normalized, 1.0 is full scale)
+    pub dc_offset: Vec<f64>,
+    /// First two channels are identical ("mono in stereo")
+    pub mono: bool,
+    /// Second channel is inverted first channel
+    pub polarity_inverted: bool,
+}
+
+impl Display for StereoStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "correlation {:.3}, imbalance {:.2} LU, DC offset",
+            self.correlation, self.imbalance
+        )?;
+        for (i, dc) in self.dc_offset.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{sep} {:.2}%", dc * 100.0)?;
+        }
+        if self.mono {
+            write!(f, ", mono in stereo")?;
+        }
+        if self.polarity_inverted {
+            write!(f, ", polarity inverted")?;
+        }
+        Ok(())
+    }
+}
+
+/// Stereo image analyzer (mastering QA)
+///
+/// Files with less than two channels have no result.
+pub struct Stereo {
+    channels: usize,
+    /// loudness of every channel on its own
+    meters: Vec<EbuR128>,
+    /// one frame of every channel (for [EbuR128])
+    buffers: Vec<Vec<f64>>,
+    n: u64,
+    sum: Vec<f64>,
+    /// sums of squares and product of first two channels
+    sum_ll: f64,
+    sum_rr: f64,
+    sum_lr: f64,
+    identical: bool,
+    inverted: bool,
+    /// any nonzero sample (silence is not mono nor inverted)
+    nonzero: bool,
+    /// Available after seeding
+    pub result: Option<StereoStats>,
+}
+
+impl Stereo {
+    pub fn new(info: &AudioInfo) -> Result<Self, SeedError> {
+        let channels = info.channels as usize;
+        Ok(Self {
+            channels,
+            meters: (0..channels)
+                .map(|_| EbuR128::new(1, info.rate, Mode::I))
+                .collect::<Result<_, _>>()?,
+            buffers: vec![Vec::new(); channels],
+            n: 0,
+            sum: vec![0.0; channels],
+            sum_ll: 0.0,
+            sum_rr: 0.0,
+            sum_lr: 0.0,
+            identical: true,
+            inverted: true,
+            nonzero: false,
+            result: None,
+        })
+    }
+}
+
+impl Analyzer for Stereo {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        if self.channels < 2 {
+            return Ok(());
+        }
+        for b in self.buffers.iter_mut() {
+            b.clear();
+        }
+        let mut left = 0.0;
+        for_each_sample(frame, self.channels, |c, x| {
+            self.buffers[c].push(x);
+            self.sum[c] += x;
+            match c {
+                0 => left = x,
+                1 => {
+                    self.sum_ll += left * left;
+                    self.sum_rr += x * x;
+                    self.sum_lr += left * x;
+                    self.identical &= left == x;
+                    self.inverted &= left == -x;
+                    self.nonzero |= left != 0.0;
+                    self.n += 1;
+                }
+                _ => {}
+            }
+        });
+        for (meter, buffer) in self.meters.iter_mut().zip(&self.buffers) {
+            meter.add_frames_f64(buffer)?;
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        if self.channels < 2 || self.n == 0 {
+            return Ok(());
+        }
+        let n = self.n as f64;
+        let (mean_l, mean_r) = (self.sum[0] / n, self.sum[1] / n);
+        let cov = self.sum_lr / n - mean_l * mean_r;
+        let var_l = self.sum_ll / n - mean_l * mean_l;
+        let var_r = self.sum_rr / n - mean_r * mean_r;
+        let correlation = if var_l > 0.0 && var_r > 0.0 {
+            (cov / (var_l * var_r).sqrt()).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        let loudness = self
+            .meters
+            .iter()
+            .map(|m| m.loudness_global())
+            .collect::<Result<Vec<_>, _>>()?;
+        // silent channels are -inf
+        let finite = loudness.iter().copied().filter(|l| l.is_finite());
+        let imbalance =
+            finite.clone().fold(f64::NEG_INFINITY, f64::max) - finite.fold(f64::INFINITY, f64::min);
+        self.result = Some(StereoStats {
+            correlation,
+            loudness,
+            imbalance: if imbalance.is_finite() {
+                imbalance
+            } else {
+                0.0
+            },
+            dc_offset: self.sum.iter().map(|s| s / n).collect(),
+            mono: self.nonzero && self.identical,
+            polarity_inverted: self.nonzero && self.inverted,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeders::FrameType;
+
+    /// 2 s of 1 kHz sine at 48 kHz, right channel is `right(left)`
+    fn stereo(right: impl Fn(f64) -> f64) -> StereoStats {
+        let mut stereo = Stereo::new(&AudioInfo::for_test(48000, 2, "pcm_f64le")).unwrap();
+        let samples: Vec<f64> = (0..2 * 48000)
+            .flat_map(|i| {
+                let left = 0.5 * (2.0 * std::f64::consts::PI * f64::from(i) / 48.0).sin();
+                [left, right(left)]
+            })
+            .collect();
+        stereo
+            .feed(&Frame::F64(FrameType::Packed(&samples)))
+            .unwrap();
+        stereo.finalize().unwrap();
+        stereo.result.unwrap()
+    }
+
+    #[test]
+    fn mono_in_stereo() {
+        let stats = stereo(|l| l);
+        assert!(stats.mono && !stats.polarity_inverted);
+        assert!((stats.correlation - 1.0).abs() < 1e-9);
+        assert!(stats.imbalance.abs() < 1e-6);
+    }
+
+    #[test]
+    fn polarity_inverted() {
+        let stats = stereo(|l| -l);
+        assert!(!stats.mono && stats.polarity_inverted);
+        assert!((stats.correlation + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn imbalance_and_dc_offset() {
+        // half amplitude is 6.02 LU quieter
+        let stats = stereo(|l| l / 2.0 + 0.1);
+        assert!(!stats.mono && !stats.polarity_inverted);
+        assert!((stats.correlation - 1.0).abs() < 1e-9);
+        assert!((stats.imbalance - 6.02).abs() < 0.01);
+        assert!(stats.dc_offset[0].abs() < 1e-9);
+        assert!((stats.dc_offset[1] - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mono_file_has_no_result() {
+        let mut stereo = Stereo::new(&AudioInfo::for_test(48000, 1, "pcm_f64le")).unwrap();
+        stereo
+            .feed(&Frame::F64(FrameType::Packed(&[0.5; 48000])))
+            .unwrap();
+        stereo.finalize().unwrap();
+        assert!(stereo.result.is_none());
+    }
+}
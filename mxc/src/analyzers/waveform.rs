@@ -0,0 +1,196 @@
+use std::io::Write;
+
+use super::{for_each_sample, Analyzer};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Frame};
+
+/// Default number of samples per pixel (same as audiowaveform)
+pub const WAVEFORM_ZOOM: u32 = 256;
+
+/// Min/max peak pairs of (downmixed) audio for waveform display
+///
+/// Output is compatible with [audiowaveform](https://github.com/bbc/audiowaveform)
+/// binary (`.dat`, version 1) and JSON format.
+pub struct Waveform {
+    rate: u32,
+    channels: usize,
+    /// samples per pixel
+    zoom: u32,
+    /// 8 or 16
+    bits: u32,
+    /// sum of channels of current instant
+    sum: f64,
+    /// instants in current pixel
+    count: u32,
+    min: i16,
+    max: i16,
+    /// Min/max pairs (16 bit, filled while seeding)
+    pub data: Vec<(i16, i16)>,
+}
+
+impl Waveform {
+    pub fn new(info: &AudioInfo) -> Self {
+        Self {
+            rate: info.rate,
+            channels: info.channels as usize,
+            zoom: WAVEFORM_ZOOM,
+            bits: 16,
+            sum: 0.0,
+            count: 0,
+            min: i16::MAX,
+            max: i16::MIN,
+            data: Vec::new(),
+        }
+    }
+
+    /// Number of samples per pixel (at least 2)
+    pub fn with_zoom(mut self, zoom: u32) -> Self {
+        self.zoom = zoom.max(2);
+        self
+    }
+
+    /// Resolution of output data (8 or 16 bits)
+    pub fn with_bits(mut self, bits: u32) -> Self {
+        self.bits = if bits <= 8 { 8 } else { 16 };
+        self
+    }
+
+    /// Pairs in output resolution
+    fn pairs(&self) -> impl Iterator<Item = (i16, i16)> + '_ {
+        let shift = 16 - self.bits;
+        self.data
+            .iter()
+            .map(move |&(min, max)| (min >> shift, max >> shift))
+    }
+
+    fn end_pixel(&mut self) {
+        self.data.push((self.min, self.max));
+        self.count = 0;
+        self.min = i16::MAX;
+        self.max = i16::MIN;
+    }
+
+    /// Writes audiowaveform binary data (`.dat`, version 1)
+    pub fn write_dat<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        w.write_all(&1i32.to_le_bytes())?;
+        // flags: 8 bit resolution
+        w.write_all(&u32::from(self.bits == 8).to_le_bytes())?;
+        w.write_all(&(self.rate as i32).to_le_bytes())?;
+        w.write_all(&(self.zoom as i32).to_le_bytes())?;
+        w.write_all(&(self.data.len() as u32).to_le_bytes())?;
+        for (min, max) in self.pairs() {
+            if self.bits == 8 {
+                w.write_all(&[min as i8 as u8, max as i8 as u8])?;
+            } else {
+                w.write_all(&min.to_le_bytes())?;
+                w.write_all(&max.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes audiowaveform JSON data (version 2, single channel)
+    pub fn write_json<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        write!(
+            w,
+            "{{\"version\":2,\"channels\":1,\"sample_rate\":{},\"samples_per_pixel\":{},\"bits\":{},\"length\":{},\"data\":[",
+            self.rate,
+            self.zoom,
+            self.bits,
+            self.data.len()
+        )?;
+        for (i, (min, max)) in self.pairs().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(w, "{sep}{min},{max}")?;
+        }
+        writeln!(w, "]}}")
+    }
+}
+
+impl Analyzer for Waveform {
+    fn feed(&mut self, frame: &Frame) -> Result<(), SeedError> {
+        for_each_sample(frame, self.channels, |c, x| {
+            self.sum += x;
+            if c + 1 == self.channels {
+                let x = (self.sum / self.channels as f64 * 32768.0)
+                    .clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+                self.sum = 0.0;
+                self.min = self.min.min(x);
+                self.max = self.max.max(x);
+                self.count += 1;
+                if self.count == self.zoom {
+                    self.end_pixel();
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), SeedError> {
+        if self.count > 0 {
+            self.end_pixel();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeders::FrameType;
+
+    fn waveform(bits: u32) -> Waveform {
+        let mut waveform = Waveform::new(&AudioInfo::for_test(8000, 2, "pcm_s16le"))
+            .with_zoom(2)
+            .with_bits(bits);
+        // channels are averaged, last pixel is partial
+        let samples: [i16; 10] = [
+            16384,
+            16384,
+            -16384,
+            -16384, // pixel 0
+            0,
+            32767,
+            i16::MIN,
+            0, // pixel 1
+            256,
+            256, // pixel 2
+        ];
+        waveform
+            .feed(&Frame::I16(FrameType::Packed(&samples)))
+            .unwrap();
+        waveform.finalize().unwrap();
+        waveform
+    }
+
+    #[test]
+    fn min_max_pairs() {
+        assert_eq!(
+            waveform(16).data,
+            [(-16384, 16384), (-16384, 16383), (256, 256)]
+        );
+    }
+
+    #[test]
+    fn dat_format() {
+        let mut dat = Vec::new();
+        waveform(8).write_dat(&mut dat).unwrap();
+        let header: Vec<i32> = dat[..20]
+            .chunks(4)
+            .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        // version, 8 bit flag, rate, zoom, length
+        assert_eq!(header, [1, 1, 8000, 2, 3]);
+        assert_eq!(dat[20..], [192, 64, 192, 63, 1, 1]);
+    }
+
+    #[test]
+    fn json_format() {
+        let mut json = Vec::new();
+        waveform(16).write_json(&mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"version\":2,\"channels\":1,\"sample_rate\":8000,\"samples_per_pixel\":2,\"bits\":16,\"length\":3,\"data\":[-16384,16384,-16384,16383,256,256]}\n"
+        );
+    }
+}
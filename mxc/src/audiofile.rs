@@ -2,29 +2,69 @@ use std::path::{Path, PathBuf};
 
 use drmeter::DRMeter;
 use ebur128::{EbuR128, Mode};
+#[cfg(feature = "symphonia")]
+use log::debug;
 
+use crate::analyzers::{
+    Analyzer, Clipping, ClippingStats, LevelStats, Levels, LoudnessCurve, LoudnessPoint,
+    Resolution, Silence, SilenceStats, SpectralSummary, Spectrum, Stereo, StereoStats,
+    TrueResolution, Waveform,
+};
+use crate::diagnostics::Diagnostic;
 use crate::error::{Error, NError, SeedError};
 use crate::fftag::FFtag;
-use crate::nerics::Nerics;
+use crate::journal::{JournalEntry, JOURNAL_TAGS};
 use crate::options;
-use crate::replay_gain::{track_rg, ReplayGain};
-use crate::seeders::{Frame, FrameType, Seeder};
+use crate::pipeline::seed_pipelined;
+use crate::replay_gain::{track_rg, ReplayGain, StoredRg, Target, RG_TAGS};
+use crate::safe_write::{safe_write, Expected};
+#[cfg(feature = "symphonia")]
+use crate::seeders::SymphoniaSeeder;
+use crate::seeders::{AudioInfo, Corruption, Seeder};
+#[cfg(feature = "lofty")]
+use crate::taggers::Lofty;
+#[cfg(feature = "taglib")]
+use crate::taggers::TagLib;
 use crate::taggers::{Tagger, WriteOptions};
 
 pub type DRscore = u8;
 
+/// Tag with track DR score (foobar2000 foo_dr convention)
+pub const DR_TAG: &str = "DYNAMIC RANGE";
+
+/// Tag with album DR score (foobar2000 foo_dr convention)
+pub const ALBUM_DR_TAG: &str = "ALBUM DYNAMIC RANGE";
+
 /// This struct represents one file. Each file has its own:
 /// - Tagger (metadata reader)
-/// - Filler (decoder; that generates samples from file)
+/// - Seeder (decoder; that generates samples from file)
+///
+/// Both are chosen at runtime (see [options::Decoder] and [options::Tagger]).
 pub struct AudioFile {
     /// FIle that is this struct about
     pub file: PathBuf,
 
-    internal: FFtag,
+    /// Decoder
+    seeder: Box<dyn Seeder + Send>,
+
+    /// Metadata writer
+    tagger: Box<dyn Tagger + Send>,
+
+    /// Backend of [AudioFile::seeder] (needed to verify temporary copies)
+    decoder_kind: options::Decoder,
 
-    /// This is for progress bar
+    /// Backend of [AudioFile::tagger] (needed to tag temporary copies)
+    tagger_kind: options::Tagger,
+
+    /// Write tags into temporary copy, verify it and atomically replace file with it
+    pub safe_write: bool,
+
+    /// This is for progress bar (estimated number of samples per channel)
     pub len: u64,
 
+    /// Exact number of decoded samples (per channel), available after seeding
+    pub samples: Option<u64>,
+
     /// Here lies [EbuR128] instance
     pub ebur: Option<EbuR128>,
 
@@ -37,159 +77,338 @@ pub struct AudioFile {
     /// Here are album RG results stored after being filled
     pub album_rg: Option<ReplayGain>,
 
+    /// ReplayGain that was in tags before (available after [AudioFile::read_stored_rg])
+    pub stored_rg: Option<StoredRg>,
+
     /// Here is DR score that is available right after seeding (if enabled)
     pub dr_score: Option<DRscore>,
 
     /// Here is DR score
     pub album_dr_score: Option<DRscore>,
+
+    /// Additional user registered analyzers that are fed alongside built-in ones
+    analyzers: Vec<Box<dyn Analyzer>>,
 }
 
 pub const NONE: Option<fn(u64)> = None::<fn(u64)>;
 
+/// Max difference (in seconds) between decoded and declared duration
+/// that is still considered fine (encoder delay and padding fit in it).
+pub const DURATION_TOLERANCE: f64 = 0.1;
+
+/// Opens decoder (symphonia falls back to FFmpeg for formats it does not support)
+pub(crate) fn open_seeder(
+    path: &Path,
+    decoder: options::Decoder,
+) -> Result<(Box<dyn Seeder + Send>, u64), NError> {
+    match decoder {
+        #[cfg(feature = "symphonia")]
+        options::Decoder::Symphonia => match SymphoniaSeeder::new(path) {
+            Ok((s, len)) => return Ok((Box::new(s), len)),
+            Err(NError::Unsupported(e)) => {
+                debug!("Symphonia does not support {e}, falling back to FFmpeg")
+            }
+            Err(e) => return Err(e),
+        },
+        #[cfg(not(feature = "symphonia"))]
+        options::Decoder::Symphonia => {
+            return Err(NError::Unsupported(
+                "symphonia (mxc was built without it)".to_owned(),
+            ))
+        }
+        options::Decoder::FFmpeg => {}
+    }
+    let (ff, len) = FFtag::new(path)?;
+    Ok((Box::new(ff), len))
+}
+
+pub(crate) fn open_tagger(
+    path: &Path,
+    tagger: options::Tagger,
+) -> Result<Box<dyn Tagger + Send>, NError> {
+    match tagger {
+        #[cfg(feature = "taglib")]
+        options::Tagger::TagLib => Ok(Box::new(TagLib::new(path)?)),
+        #[cfg(feature = "lofty")]
+        options::Tagger::Lofty => Ok(Box::new(Lofty::new(path))),
+        #[allow(unreachable_patterns)]
+        x => Err(NError::Unsupported(format!(
+            "{x:?} tagger (mxc was built without it)"
+        ))),
+    }
+}
+
 impl AudioFile {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, NError> {
-        let (internal, len) = crate::fftag::FFtag::new(path.as_ref())?;
+        Self::with_backends(
+            path,
+            options::Decoder::default(),
+            options::Tagger::default(),
+        )
+    }
+
+    /// Same as [AudioFile::new], but with explicitly chosen backends
+    pub fn with_backends<P: AsRef<Path>>(
+        path: P,
+        decoder: options::Decoder,
+        tagger: options::Tagger,
+    ) -> Result<Self, NError> {
+        let (seeder, len) = open_seeder(path.as_ref(), decoder)?;
         Ok(Self {
             file: path.as_ref().to_path_buf(),
-            internal,
+            seeder,
+            tagger: open_tagger(path.as_ref(), tagger)?,
+            decoder_kind: decoder,
+            tagger_kind: tagger,
+            safe_write: false,
             len,
+            samples: None,
             ebur: None,
             dr_meter: None,
             track_rg: None,
             album_rg: None,
+            stored_rg: None,
             dr_score: None,
             album_dr_score: None,
+            analyzers: Vec::new(),
         })
     }
 
-    // TODO: make this pipeline async (constantly suck data from ffmpeg and feed it to libs)
+    /// Technical stream metadata (also useful for constructing [Analyzer]s)
+    pub fn info(&self) -> &AudioInfo {
+        self.seeder.info()
+    }
+
+    /// Exact duration in seconds (computed from decoded samples), available after seeding
+    pub fn duration(&self) -> Option<f64> {
+        self.samples.map(|s| s as f64 / f64::from(self.info().rate))
+    }
+
+    /// Duration in seconds as declared by container
+    pub fn declared_duration(&self) -> Option<f64> {
+        self.info().duration
+    }
+
+    /// True if decoded duration differs from declared one by more than [DURATION_TOLERANCE]
+    ///
+    /// This usually means file is truncated or has broken header.
+    pub fn duration_mismatch(&self) -> bool {
+        match (self.duration(), self.declared_duration()) {
+            (Some(decoded), Some(declared)) => (decoded - declared).abs() > DURATION_TOLERANCE,
+            _ => false,
+        }
+    }
+
+    /// Damage found while seeding (bad packets are only skipped with forced seeding)
+    pub fn corruption(&self) -> &Corruption {
+        self.seeder.corruption()
+    }
+
+    /// Messages that decoder emitted while opening and seeding this file
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.seeder.diagnostics()
+    }
+
+    /// True if file has damaged packets or its decoded duration does not match declared one
+    pub fn is_damaged(&self) -> bool {
+        self.corruption().is_damaged() || self.duration_mismatch()
+    }
+
+    /// Spectral summary with transcode detection (if [Spectrum] or [Resolution] analyzer was registered)
+    pub fn spectrum(&self) -> Option<&SpectralSummary> {
+        match self.analyzer::<Spectrum>() {
+            Some(spectrum) => spectrum.summary.as_ref(),
+            // resolution analyzer computes the same spectrum
+            None => self.analyzer::<Resolution>()?.spectrum(),
+        }
+    }
+
+    /// Effective bit depth and upsampling detection (if [Resolution] analyzer was registered)
+    pub fn resolution(&self) -> Option<&TrueResolution> {
+        self.analyzer::<Resolution>()?.result.as_ref()
+    }
+
+    /// Sample peak and clipping statistics (if [Clipping] analyzer was registered)
+    pub fn clipping(&self) -> Option<&ClippingStats> {
+        self.analyzer::<Clipping>()?.result.as_ref()
+    }
+
+    /// Leading/trailing silence and gaps (if [Silence] analyzer was registered)
+    pub fn silence(&self) -> Option<&SilenceStats> {
+        self.analyzer::<Silence>()?.result.as_ref()
+    }
+
+    /// Stereo image statistics (if [Stereo] analyzer was registered and file has 2+ channels)
+    pub fn stereo(&self) -> Option<&StereoStats> {
+        self.analyzer::<Stereo>()?.result.as_ref()
+    }
+
+    /// Peak and RMS of whole track (if [Levels] analyzer was registered)
+    pub fn levels(&self) -> Option<&LevelStats> {
+        self.analyzer::<Levels>()?.result.as_ref()
+    }
+
+    /// Momentary/short-term loudness over time (if [LoudnessCurve] analyzer was registered)
+    pub fn loudness_curve(&self) -> Option<&[LoudnessPoint]> {
+        Some(&self.analyzer::<LoudnessCurve>()?.points)
+    }
+
+    /// Waveform min/max pairs (if [Waveform] analyzer was registered)
+    pub fn waveform(&self) -> Option<&[(i16, i16)]> {
+        Some(&self.analyzer::<Waveform>()?.data)
+    }
+
+    /// Register additional [Analyzer] that will be fed on next [AudioFile::seed]
+    pub fn add_analyzer<A: Analyzer + 'static>(&mut self, analyzer: A) {
+        self.analyzers.push(Box::new(analyzer));
+    }
+
+    /// Get first registered [Analyzer] of type `A`
+    pub fn analyzer<A: Analyzer + 'static>(&self) -> Option<&A> {
+        self.analyzers
+            .iter()
+            .find_map(|a| (**a).as_any().downcast_ref::<A>())
+    }
+
+    /// Get first registered [Analyzer] of type `A` as mutable
+    pub fn analyzer_mut<A: Analyzer + 'static>(&mut self) -> Option<&mut A> {
+        self.analyzers
+            .iter_mut()
+            .find_map(|a| (**a).as_any_mut().downcast_mut::<A>())
+    }
+
     /// Feed all libraries that are needed with data.
     /// Those libraries consumes data on frame basis (so we do not store whole file in memory)
     /// and do their own computations.
     ///
-    /// Forced option will allow damaged files
+    /// Forced option will allow damaged files:
+    /// bad packets are skipped and recorded in [AudioFile::corruption]
     pub fn seed<F>(
         &mut self,
         ebur: bool,
         dr_meter: bool,
         forced: bool,
+        progress: Option<F>,
+    ) -> Result<(), SeedError>
+    where
+        F: FnMut(u64),
+    {
+        self.seed_inner(ebur, dr_meter, forced, false, progress)
+    }
+
+    /// Same as [AudioFile::seed], but decoding is done on current thread
+    /// and each analyzer is fed on its own thread.
+    ///
+    /// This allows one long file to use several cores.
+    pub fn seed_pipelined<F>(
+        &mut self,
+        ebur: bool,
+        dr_meter: bool,
+        forced: bool,
+        progress: Option<F>,
+    ) -> Result<(), SeedError>
+    where
+        F: FnMut(u64),
+    {
+        self.seed_inner(ebur, dr_meter, forced, true, progress)
+    }
+
+    fn seed_inner<F>(
+        &mut self,
+        ebur: bool,
+        dr_meter: bool,
+        forced: bool,
+        pipelined: bool,
         mut progress: Option<F>,
     ) -> Result<(), SeedError>
     where
         F: FnMut(u64),
     {
-        let info = self.internal.info();
+        let (rate, channels) = (self.info().rate, self.info().channels);
         if ebur {
             self.ebur = Some(EbuR128::new(
-                info.channels,
-                info.rate,
+                channels,
+                rate,
                 // global loudnes | loudness range
                 // EBUR128_MODE_S and EBUR128_MODE_SAMPLE_PEAK are also hidden inside
                 Mode::I | Mode::LRA | Mode::TRUE_PEAK,
             )?);
         }
         if dr_meter {
-            self.dr_meter = Some(DRMeter::new(info.channels, info.rate)?)
+            self.dr_meter = Some(DRMeter::new(channels, rate)?)
+        }
+        let mut analyzers: Vec<&mut dyn Analyzer> = Vec::new();
+        if let Some(e) = self.ebur.as_mut() {
+            analyzers.push(e);
+        }
+        if let Some(dr) = self.dr_meter.as_mut() {
+            analyzers.push(dr);
         }
-        self.internal.seed(forced, |d, frame| {
-            // send progress if required
+        analyzers.extend(
+            self.analyzers
+                .iter_mut()
+                .map(|a| &mut **a as &mut dyn Analyzer),
+        );
+        // send progress if required
+        let mut report = |d| {
             if let Some(p) = progress.as_mut() {
                 p(d)
             }
-            match frame {
-                Frame::I16(FrameType::Packed(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_i16(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_i16(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::I32(FrameType::Packed(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_i32(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_i32(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::F32(FrameType::Packed(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_f32(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_f32(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::F64(FrameType::Packed(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_f64(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_f64(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::I16(FrameType::Planar(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_planar_i16(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_planar_i16(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::I32(FrameType::Planar(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_planar_i32(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_planar_i32(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::F32(FrameType::Planar(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_planar_f32(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_planar_f32(x)?;
-                    }
-                    Ok(())
-                }
-                Frame::F64(FrameType::Planar(x)) => {
-                    if let Some(e) = self.ebur.as_mut() {
-                        e.add_frames_planar_f64(x)?;
-                    }
-                    if let Some(dr) = self.dr_meter.as_mut() {
-                        dr.add_frames_planar_f64(x)?;
-                    }
-                    Ok(())
+        };
+        if pipelined {
+            seed_pipelined(&mut *self.seeder, forced, analyzers, &mut report)?;
+        } else {
+            self.seeder.seed(forced, &mut |d, frame| {
+                report(d);
+                for analyzer in analyzers.iter_mut() {
+                    analyzer.feed(&frame)?;
                 }
-            }
-        })?;
-        // finalize progress
-        if let Some(p) = progress.as_mut() {
-            p(self.len)
+                Ok(())
+            })?;
         }
+        self.samples = Some(self.seeder.samples());
+        // finalize progress
+        report(self.len);
         // finalize and store DR score
         if let Some(dr) = self.dr_meter.as_mut() {
-            dr.finalize()?;
+            Analyzer::finalize(dr)?;
             self.dr_score = Some(dr.dr_score()?);
         }
+        for analyzer in self.analyzers.iter_mut() {
+            analyzer.finalize()?;
+        }
         Ok(())
     }
 
+    /// Runs tagging operation (on temporary copy if [AudioFile::safe_write] is enabled,
+    /// that must read back `expected` tags)
+    fn tag_with<F>(&self, expected: &Expected, op: F) -> Result<(), crate::error::MetaError>
+    where
+        F: FnOnce(&dyn Tagger) -> Result<(), crate::error::MetaError>,
+    {
+        if self.safe_write {
+            safe_write(
+                &self.file,
+                self.decoder_kind,
+                self.tagger_kind,
+                expected,
+                op,
+            )
+        } else {
+            op(self.tagger.as_ref())
+        }
+    }
+
     pub fn delete_tags(
         &mut self,
         strip: bool,
         id3v2version: options::Id3v2version,
     ) -> Result<(), crate::error::MetaError> {
-        self.internal
-            .do_meta(strip, id3v2version, None, self.track_rg, self.album_rg)
+        let (track_rg, album_rg) = (self.track_rg, self.album_rg);
+        // only readability is checked, as RG could remain in other tag types (e.g. APE)
+        self.tag_with(&Expected::Tags(&[]), |t| {
+            t.do_meta(strip, id3v2version, None, track_rg, album_rg)
+        })
     }
 
     pub fn write_tags(
@@ -201,27 +420,97 @@ impl AudioFile {
         lowercase: bool,
         non_standard_opus: bool,
     ) -> Result<(), crate::error::MetaError> {
-        self.internal.do_meta(
-            strip,
-            id3v2version,
-            Some(WriteOptions {
-                extended,
-                unit: unit.to_owned(),
-                lowercase,
-                non_standard_opus,
-            }),
-            self.track_rg,
-            self.album_rg,
-        )
+        let (track_rg, album_rg) = (self.track_rg, self.album_rg);
+        let expected = Expected::ReplayGain {
+            track: track_rg.ok_or(crate::error::MetaError::NotComputed)?,
+            album: album_rg,
+        };
+        self.tag_with(&expected, |t| {
+            t.do_meta(
+                strip,
+                id3v2version,
+                Some(WriteOptions {
+                    extended,
+                    unit: unit.to_owned(),
+                    lowercase,
+                    non_standard_opus,
+                }),
+                track_rg,
+                album_rg,
+            )
+        })
+    }
+
+    /// Reads ReplayGain that is currently stored in tags into [AudioFile::stored_rg]
+    pub fn read_stored_rg(&mut self) -> Result<(), crate::error::MetaError> {
+        let values = self.tagger.read_custom(&RG_TAGS)?;
+        self.stored_rg = Some(StoredRg::from_tags(&values));
+        Ok(())
+    }
+
+    /// Values of `keys` that are currently stored in tags
+    pub fn read_tags(&self, keys: &[&str]) -> Result<Vec<Option<String>>, crate::error::MetaError> {
+        self.tagger.read_custom(keys)
+    }
+
+    /// Current RG tags of file (see [JOURNAL_TAGS]) and `extra` tags (e.g. DR tags that will be written)
+    /// to be recorded in [Journal](crate::journal::Journal)
+    pub fn journal_entry(&self, extra: &[&str]) -> Result<JournalEntry, crate::error::MetaError> {
+        let keys: Vec<&str> = JOURNAL_TAGS.iter().chain(extra).copied().collect();
+        let values = self.tagger.read_custom(&keys)?;
+        Ok(JournalEntry {
+            file: self.file.clone(),
+            tags: keys.into_iter().map(str::to_owned).zip(values).collect(),
+        })
+    }
+
+    /// Restores tags recorded in journal (missing tags are removed)
+    pub fn restore_tags(
+        &mut self,
+        id3v2version: options::Id3v2version,
+        entry: &JournalEntry,
+    ) -> Result<(), crate::error::MetaError> {
+        let tags: Vec<_> = entry
+            .tags
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        self.tag_with(&Expected::Tags(&tags), |t| t.do_custom(id3v2version, &tags))
+    }
+
+    /// Writes DR scores into `track_tag` and `album_tag` (see [DR_TAG] and [ALBUM_DR_TAG]).
+    /// Album tag is removed if there is no album score.
+    pub fn write_dr_tags(
+        &mut self,
+        id3v2version: options::Id3v2version,
+        track_tag: &str,
+        album_tag: &str,
+    ) -> Result<(), crate::error::MetaError> {
+        let dr_score = self.dr_score.ok_or(crate::error::MetaError::NotComputed)?;
+        let tags = [
+            (track_tag, Some(dr_score.to_string())),
+            (album_tag, self.album_dr_score.map(|x| x.to_string())),
+        ];
+        self.tag_with(&Expected::Tags(&tags), |t| t.do_custom(id3v2version, &tags))
+    }
+
+    pub fn delete_dr_tags(
+        &mut self,
+        id3v2version: options::Id3v2version,
+        track_tag: &str,
+        album_tag: &str,
+    ) -> Result<(), crate::error::MetaError> {
+        let tags = [(track_tag, None), (album_tag, None)];
+        self.tag_with(&Expected::Tags(&tags), |t| t.do_custom(id3v2version, &tags))
     }
 
-    pub fn track_gain(&mut self, mut pregain: f64, non_standard_opus: bool) -> Result<(), Error> {
+    pub fn track_gain(&mut self, mut target: Target, non_standard_opus: bool) -> Result<(), Error> {
         if self.track_rg.is_none() {
             if let Some(ebur) = self.ebur.as_ref() {
-                if !non_standard_opus && self.internal.is_opus() {
-                    pregain -= 5.0;
+                if !non_standard_opus && self.seeder.is_opus() {
+                    target = target.opus();
                 }
-                self.track_rg = Some(track_rg(ebur, pregain)?);
+                self.track_rg = Some(track_rg(ebur, target)?);
             } else {
                 return Err(Error::NotComputed);
             }
@@ -230,7 +519,7 @@ impl AudioFile {
     }
 
     pub fn fill_album(&mut self, rg: ReplayGain, non_standard_opus: bool) {
-        if !non_standard_opus && self.internal.is_opus() {
+        if !non_standard_opus && self.seeder.is_opus() {
             todo!("OPUS albums are not supported")
         } else {
             self.album_rg = Some(rg);
@@ -1,13 +1,16 @@
+use std::path::PathBuf;
 use std::process::exit;
 
+use console::style;
 use indicatif::{MultiProgress, ParallelProgressIterator, ProgressBar};
 use log::debug;
+use mxc::journal::{read_journal, Journal};
 use mxc::walker::{self, RGE};
 use options::Output;
 use rayon::prelude::*;
 
-use crate::printe::progress_style;
-use crate::worker::{delete_on_rge, mach_rge};
+use crate::printe::{print_err, progress_style};
+use crate::worker::{compliance_on_rge, delete_on_rge, mach_rge, spectrogram_on_rge, undo_entry};
 
 mod options;
 mod printe; //rs
@@ -31,12 +34,13 @@ fn main() -> Result<(), mxc::Error> {
         options::Command::Delete(d) => {
             let v = walk_and_ask(&d.paths, d.yes, d.output)?;
             build_thread_pool(d.jobs);
+            let journal = open_journal(d.journal.as_ref().filter(|_| !d.dry_run))?;
             // this is very fast on per file basis
             // so the only relevant progress bar is overall status
             v.par_iter()
                 .progress_count(v.len() as u64)
                 .with_style(progress_style())
-                .try_for_each(|x| delete_on_rge(x, d))
+                .try_for_each(|x| delete_on_rge(x, d, journal.as_ref()))
         }
         options::Command::Calc(o) => {
             let v = walk_and_ask(&o.paths, o.yes, o.output)?;
@@ -51,7 +55,9 @@ fn main() -> Result<(), mxc::Error> {
                     mach_rge(
                         x,
                         false,
+                        o.pipeline,
                         o,
+                        None,
                         mp.as_ref().map(|m| {
                             m.add(
                                 ProgressBar::new(100)
@@ -67,6 +73,7 @@ fn main() -> Result<(), mxc::Error> {
         options::Command::Write(o) => {
             let v = walk_and_ask(&o.paths, o.yes, o.output)?;
             build_thread_pool(o.jobs);
+            let journal = open_journal(o.journal.as_ref().filter(|_| !o.dry_run))?;
             let mp = if o.output.is_tui() {
                 Some(MultiProgress::new())
             } else {
@@ -76,8 +83,10 @@ fn main() -> Result<(), mxc::Error> {
                 .map(|x| {
                     mach_rge(
                         x,
-                        true,
+                        !o.dry_run,
+                        o.pipeline,
                         o,
+                        journal.as_ref(),
                         mp.as_ref().map(|m| {
                             m.add(
                                 ProgressBar::new(0)
@@ -90,6 +99,99 @@ fn main() -> Result<(), mxc::Error> {
                 .collect::<Result<Vec<()>, mxc::Error>>()?;
             Ok(())
         }
+        options::Command::CheckCompliance(o) => {
+            let v = walk_and_ask(&o.paths, o.yes, o.output)?;
+            build_thread_pool(o.jobs);
+            let mp = if o.output.is_tui() {
+                Some(MultiProgress::new())
+            } else {
+                None
+            };
+            v.par_iter()
+                .map(|x| {
+                    compliance_on_rge(
+                        x,
+                        o,
+                        mp.as_ref().map(|m| {
+                            m.add(
+                                ProgressBar::new(0)
+                                    .with_style(progress_style())
+                                    .with_message(format!("{x}")),
+                            )
+                        }),
+                    )
+                })
+                .collect::<Result<Vec<()>, mxc::Error>>()?;
+            Ok(())
+        }
+        options::Command::Spectrogram(o) => {
+            let v = walk_and_ask(&o.paths, o.yes, o.output)?;
+            build_thread_pool(o.jobs);
+            let mp = if o.output.is_tui() {
+                Some(MultiProgress::new())
+            } else {
+                None
+            };
+            v.par_iter()
+                .map(|x| {
+                    spectrogram_on_rge(
+                        x,
+                        o,
+                        mp.as_ref().map(|m| {
+                            m.add(
+                                ProgressBar::new(0)
+                                    .with_style(progress_style())
+                                    .with_message(format!("{x}")),
+                            )
+                        }),
+                    )
+                })
+                .collect::<Result<Vec<()>, mxc::Error>>()?;
+            Ok(())
+        }
+        options::Command::Undo(u) => {
+            let mut journals = Vec::new();
+            for path in &u.journals {
+                journals.push(read_journal(path)?);
+            }
+            // newer runs are undone first, so the oldest state wins
+            journals.sort_by(|a, b| b.started.cmp(&a.started));
+            let entries: Vec<_> = journals.into_iter().map(|j| j.entries).collect();
+            println!(
+                "Restoring tags of {} files.",
+                entries.iter().map(Vec::len).sum::<usize>()
+            );
+            if !(u.yes || confirm()) {
+                println!("User said no!");
+                exit(0)
+            }
+            build_thread_pool(u.jobs);
+            let mut failed = 0;
+            for entries in &entries {
+                failed += entries
+                    .par_iter()
+                    .progress_count(entries.len() as u64)
+                    .with_style(progress_style())
+                    .filter(|entry| match undo_entry(entry, u) {
+                        Ok(()) => false,
+                        Err(err) => {
+                            print_err(format!(
+                                "[{}] {} {}\n",
+                                style('x').red().bold(),
+                                entry.file.display(),
+                                style(err.to_string()).red()
+                            ));
+                            true
+                        }
+                    })
+                    .count();
+            }
+            if failed > 0 {
+                println!("Could not restore {failed} files.");
+                exit(1)
+            }
+            Ok(())
+        }
         options::Command::Version(_) => options::version(),
     }
 }
@@ -131,6 +233,11 @@ fn walk_and_ask(paths: &[String], yes: bool, output: Output) -> Result<Vec<RGE>,
     }
 }
 
+/// Creates undo journal if requested
+fn open_journal(path: Option<&PathBuf>) -> Result<Option<Journal>, mxc::Error> {
+    Ok(path.map(Journal::create).transpose()?)
+}
+
 fn build_thread_pool(j: usize) {
     rayon::ThreadPoolBuilder::new()
         .num_threads(j)
@@ -1,9 +1,13 @@
 use std::convert::Infallible;
+use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
 
 use gumdrop::Options;
-use mxc::options::Id3v2version;
+use log::LevelFilter;
+use mxc::compliance::{Preset, PRESETS};
+use mxc::options::{Decoder, FrequencyScale, Id3v2version, Tagger};
+use mxc::replay_gain::Target;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -50,6 +54,80 @@ impl FromStr for Output {
     }
 }
 
+/// Format of exported loudness curve
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CurveFormat {
+    Csv,
+    Json,
+}
+
+impl CurveFormat {
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "loudness.csv",
+            Self::Json => "loudness.json",
+        }
+    }
+}
+
+impl FromStr for CurveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown curve format: {s} (csv or json)")),
+        }
+    }
+}
+
+/// Format of exported waveform (audiowaveform compatible)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WaveformFormat {
+    Dat,
+    Json,
+}
+
+impl WaveformFormat {
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Dat => "waveform.dat",
+            Self::Json => "waveform.json",
+        }
+    }
+}
+
+impl FromStr for WaveformFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dat" => Ok(Self::Dat),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown waveform format: {s} (dat or json)")),
+        }
+    }
+}
+
+/// Up to which level decoder diagnostics are printed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Verbosity(pub LevelFilter);
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        Self(LevelFilter::Error)
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = log::ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
 /// MXC computes different properties of music files (for example ReplayGain 2, DR score) and then print or store them.
 #[derive(Debug, Options)]
 pub struct MxcOptions {
@@ -85,6 +163,12 @@ pub enum Command {
     Calc(Opts),
     #[options(help = "Calculate & write tags")]
     Write(Opts),
+    #[options(help = "Check loudness compliance with broadcast standards and streaming platforms")]
+    CheckCompliance(ComplianceOpts),
+    #[options(help = "Render spectrogram of every file as PNG image")]
+    Spectrogram(SpectrogramOpts),
+    #[options(help = "Restore ReplayGain and DR tags recorded in journal")]
+    Undo(UndoOpts),
     #[options(help = "Show version numbers of underlying libraries")]
     Version(HelpOpts),
 }
@@ -144,6 +228,45 @@ pub struct DeleteOpts {
     #[options(help = "Outputing mode (Tui, PrettyPrint or Log if you want to pipe output)")]
     pub output: Output,
 
+    /// Do not delete
+    #[options(
+        help = "Show ReplayGain tag values that would be deleted, but do not delete anything"
+    )]
+    pub dry_run: bool,
+
+    /// Undo journal
+    #[options(
+        help = "Record previous ReplayGain and DR tags into new journal FILE (restore them with mxc undo)",
+        meta = "FILE"
+    )]
+    pub journal: Option<PathBuf>,
+
+    /// Crash-safe tagging
+    #[options(
+        help = "Tag temporary copy of every file and atomically replace the original with it (slower, but crash-safe)"
+    )]
+    pub safe_write: bool,
+
+    /// Delete DR tags
+    #[options(help = "Also delete DR tags")]
+    pub dr: bool,
+
+    /// DR tag name
+    #[options(
+        help = "Name of track DR tag",
+        meta = "NAME",
+        default_expr = "mxc::DR_TAG.to_owned()"
+    )]
+    pub dr_tag: String,
+
+    /// Album DR tag name
+    #[options(
+        help = "Name of album DR tag",
+        meta = "NAME",
+        default_expr = "mxc::ALBUM_DR_TAG.to_owned()"
+    )]
+    pub album_dr_tag: String,
+
     /// Force stripping tags
     #[options(
         help = "Strip tag types other than ID3v2 from MP2/MP3 files (i.e. ID3v1, APEv2). Strip tag types other than APEv2 from WavPack/APE files (i.e. ID3v1)"
@@ -156,6 +279,188 @@ pub struct DeleteOpts {
         meta = "X"
     )]
     pub id3v2version: Id3v2version,
+
+    /// Decoding backend
+    #[options(
+        help = "Decoding backend (ffmpeg or symphonia, that falls back to ffmpeg)",
+        meta = "D"
+    )]
+    pub decoder: Decoder,
+
+    /// Tagging backend
+    #[options(help = "Tagging backend (taglib or lofty)", meta = "T")]
+    pub tagger: Tagger,
+}
+
+// Options accepted for the `check-compliance` command
+#[derive(Debug, Options)]
+pub struct ComplianceOpts {
+    /// Contains paths to be scanned
+    ///
+    /// Folders are walked, files are threated as singles
+    #[options(free)]
+    pub paths: Vec<String>,
+
+    /// Max number of parallel jobs
+    #[options(help = "Max number of parallel jobs", default_expr = "num_cpus::get()")]
+    pub jobs: usize,
+
+    /// No questions asked
+    #[options(help = "Yes to all (aka. do not question, I trust)")]
+    pub yes: bool,
+
+    /// Outputing mode
+    #[options(help = "Outputing mode (Tui, PrettyPrint or Log if you want to pipe output)")]
+    pub output: Output,
+
+    /// Presets to check
+    #[options(
+        help = "Check only preset P (ebu, atsc, spotify, apple, youtube or tidal; can be repeated)",
+        meta = "P"
+    )]
+    pub preset: Vec<Preset>,
+
+    /// Do not check album values (track only)
+    #[options(help = "Do not check album values (track only)")]
+    pub no_album: bool,
+
+    /// Skip damaged packets instead of failing
+    #[options(help = "Skip damaged packets and keep decoding")]
+    pub allow_corrupted: bool,
+
+    /// Decoding backend
+    #[options(
+        help = "Decoding backend (ffmpeg or symphonia, that falls back to ffmpeg)",
+        meta = "D"
+    )]
+    pub decoder: Decoder,
+
+    /// Tagging backend
+    #[options(help = "Tagging backend (taglib or lofty)", meta = "T")]
+    pub tagger: Tagger,
+}
+
+impl ComplianceOpts {
+    /// Selected presets (all if none was selected)
+    pub fn presets(&self) -> Vec<Preset> {
+        if self.preset.is_empty() {
+            PRESETS.to_vec()
+        } else {
+            self.preset.clone()
+        }
+    }
+}
+
+// Options accepted for the `spectrogram` command
+#[derive(Debug, Options)]
+pub struct SpectrogramOpts {
+    /// Contains paths to be scanned
+    ///
+    /// Folders are walked, files are threated as singles
+    #[options(free)]
+    pub paths: Vec<String>,
+
+    /// Max number of parallel jobs
+    #[options(help = "Max number of parallel jobs", default_expr = "num_cpus::get()")]
+    pub jobs: usize,
+
+    /// No questions asked
+    #[options(help = "Yes to all (aka. do not question, I trust)")]
+    pub yes: bool,
+
+    /// Outputing mode
+    #[options(help = "Outputing mode (Tui, PrettyPrint or Log if you want to pipe output)")]
+    pub output: Output,
+
+    /// FFT size
+    #[options(
+        help = "Use n samples per FFT window (power of two)",
+        meta = "n",
+        default_expr = "mxc::analyzers::FFT_SIZE"
+    )]
+    pub fft_size: usize,
+
+    /// Frequency scale
+    #[options(help = "Frequency scale (linear or log)", meta = "S")]
+    pub scale: FrequencyScale,
+
+    /// Image width
+    #[options(
+        help = "Image width in pixels",
+        meta = "n",
+        default_expr = "mxc::analyzers::SPECTROGRAM_WIDTH"
+    )]
+    pub width: u32,
+
+    /// Image height
+    #[options(
+        help = "Image height in pixels",
+        meta = "n",
+        default_expr = "mxc::analyzers::SPECTROGRAM_HEIGHT"
+    )]
+    pub height: u32,
+
+    /// Where to put images
+    #[options(
+        help = "Write images into DIR instead of next to audio files",
+        meta = "DIR"
+    )]
+    pub sidecar_dir: Option<PathBuf>,
+
+    /// Skip damaged packets instead of failing
+    #[options(help = "Skip damaged packets and keep decoding")]
+    pub allow_corrupted: bool,
+
+    /// Decoding backend
+    #[options(
+        help = "Decoding backend (ffmpeg or symphonia, that falls back to ffmpeg)",
+        meta = "D"
+    )]
+    pub decoder: Decoder,
+
+    /// Tagging backend
+    #[options(help = "Tagging backend (taglib or lofty)", meta = "T")]
+    pub tagger: Tagger,
+}
+
+// Options accepted for the `undo` command
+#[derive(Debug, Options)]
+pub struct UndoOpts {
+    /// Journals to be restored
+    #[options(free)]
+    pub journals: Vec<PathBuf>,
+
+    /// Max number of parallel jobs
+    #[options(help = "Max number of parallel jobs", default_expr = "num_cpus::get()")]
+    pub jobs: usize,
+
+    /// No questions asked
+    #[options(help = "Yes to all (aka. do not question, I trust)")]
+    pub yes: bool,
+
+    /// Crash-safe tagging
+    #[options(
+        help = "Tag temporary copy of every file and atomically replace the original with it (slower, but crash-safe)"
+    )]
+    pub safe_write: bool,
+
+    /// ID3v2 version
+    #[options(
+        help = "Write ID3v2.X tags to MP2/MP3/WAV/AIFF files (only 3 and 4 are supported)",
+        meta = "X"
+    )]
+    pub id3v2version: Id3v2version,
+
+    /// Decoding backend
+    #[options(
+        help = "Decoding backend (ffmpeg or symphonia, that falls back to ffmpeg)",
+        meta = "D"
+    )]
+    pub decoder: Decoder,
+
+    /// Tagging backend
+    #[options(help = "Tagging backend (taglib or lofty)", meta = "T")]
+    pub tagger: Tagger,
 }
 
 #[derive(Debug, Options, Default)]
@@ -181,9 +486,137 @@ pub struct Opts {
     pub output: Output,
 
     /// Calculate DR score
-    #[options(help = "Calculate DR14 score")]
+    #[options(help = "Calculate DR14 score (and write it as tags)")]
     pub dr: bool,
 
+    /// foo_dr log
+    #[options(
+        help = "Write foobar2000 compatible foo_dr.txt log into album folder (implies --dr)"
+    )]
+    pub dr_log: bool,
+
+    /// DR tag name
+    #[options(
+        help = "Name of track DR tag",
+        meta = "NAME",
+        default_expr = "mxc::DR_TAG.to_owned()"
+    )]
+    pub dr_tag: String,
+
+    /// Album DR tag name
+    #[options(
+        help = "Name of album DR tag",
+        meta = "NAME",
+        default_expr = "mxc::ALBUM_DR_TAG.to_owned()"
+    )]
+    pub album_dr_tag: String,
+
+    /// Spectral analysis
+    #[options(
+        help = "Analyze spectrum and detect lossless files transcoded from lossy (fake lossless)"
+    )]
+    pub spectrum: bool,
+
+    /// Clipping
+    #[options(help = "Report sample peak, samples at full scale and clipped regions")]
+    pub clipping: bool,
+
+    /// Silence
+    #[options(help = "Report leading/trailing silence, internal gaps and digital-silent files")]
+    pub silence: bool,
+
+    /// Silence threshold
+    #[options(
+        help = "Treat audio below n dBFS as silence",
+        meta = "n",
+        default_expr = "mxc::analyzers::SILENCE_THRESHOLD"
+    )]
+    pub silence_threshold: f64,
+
+    /// Stereo image
+    #[options(
+        help = "Report L/R correlation, channel imbalance, DC offset, mono in stereo and inverted polarity"
+    )]
+    pub stereo: bool,
+
+    /// True resolution
+    #[options(help = "Detect padded bit depth and upsampled sample rate")]
+    pub resolution: bool,
+
+    /// Loudness curve
+    #[options(
+        help = "Export momentary and short-term loudness curve of every file (csv or json)",
+        meta = "FORMAT"
+    )]
+    pub loudness_curve: Option<CurveFormat>,
+
+    /// Loudness curve interval
+    #[options(
+        help = "Sample loudness curve every n seconds",
+        meta = "n",
+        default_expr = "mxc::analyzers::CURVE_INTERVAL"
+    )]
+    pub curve_interval: f64,
+
+    /// Waveform
+    #[options(
+        help = "Export audiowaveform compatible waveform of every file (dat or json)",
+        meta = "FORMAT"
+    )]
+    pub waveform: Option<WaveformFormat>,
+
+    /// Waveform zoom
+    #[options(
+        help = "Use n samples per waveform pixel",
+        meta = "n",
+        default_expr = "mxc::analyzers::WAVEFORM_ZOOM"
+    )]
+    pub waveform_zoom: u32,
+
+    /// Waveform resolution
+    #[options(
+        help = "Use n bit waveform data (8 or 16)",
+        meta = "n",
+        default_expr = "16"
+    )]
+    pub waveform_bits: u32,
+
+    /// Where to put sidecar files
+    #[options(
+        help = "Write exported files (loudness curves, waveforms, DR logs) into DIR instead of next to audio files",
+        meta = "DIR"
+    )]
+    pub sidecar_dir: Option<PathBuf>,
+
+    /// Show tag changes
+    #[options(help = "Show old and new ReplayGain tag values of every file")]
+    pub diff: bool,
+
+    /// Do not write
+    #[options(help = "Show old and new ReplayGain tag values, but do not write anything")]
+    pub dry_run: bool,
+
+    /// Flag large changes
+    #[options(
+        help = "Flag gain changes larger than n dB",
+        meta = "n",
+        default_expr = "1.0"
+    )]
+    pub diff_threshold: f64,
+
+    /// Undo journal
+    #[options(
+        help = "Record previous ReplayGain and DR tags into new journal FILE (restore them with mxc undo)",
+        meta = "FILE"
+    )]
+    pub journal: Option<PathBuf>,
+
+    /// Crash-safe tagging
+    #[options(
+        help = "Tag temporary copy of every file and atomically replace the original with it (slower, but crash-safe)"
+    )]
+    pub safe_write: bool,
+
     /// Do not calculate replay gain.
     #[options(help = "Skips feeding Ebur128, but does not produce ReplayGain results")]
     pub no_rg: bool,
@@ -213,20 +646,27 @@ pub struct Opts {
 
     /// Pregain
     #[options(
-        help = "Apply n dB/LU pre-gain value (use -5 for -23 LUFS target)",
+        help = "Apply n dB/LU pre-gain value (ignored if --target is used)",
         meta = "n",
         default_expr = "0.0"
     )]
     pub pregain: f64,
 
+    /// Target loudness
+    #[options(
+        help = "Normalize to LUFS or preset (replaygain, ebu, atsc, spotify, apple, youtube or tidal)",
+        meta = "T"
+    )]
+    pub target: Option<Target>,
+
     /// Force lowercase tags where possible
     #[options(
         help = "Force lowercase tags (MP2/MP3/MP4/ASF/WMA/WAV/AIFF only). This is non-standard, but sometimes needed"
     )]
     pub lowercase_tags: bool,
 
-    /// Force stripping tags
-    #[options(help = "Force doing corrupted files")]
+    /// Skip damaged packets instead of failing
+    #[options(help = "Skip damaged packets and keep decoding (damaged files are reported)")]
     pub allow_corrupted: bool,
 
     /// Force stripping tags
@@ -241,6 +681,30 @@ pub struct Opts {
         meta = "X"
     )]
     pub id3v2version: Id3v2version,
+
+    /// Decode and analyze on separate threads
+    #[options(
+        help = "Decode and run each analyzer on its own thread (helps when there are less Album/Single units than jobs)"
+    )]
+    pub pipeline: bool,
+
+    /// Decoding backend
+    #[options(
+        help = "Decoding backend (ffmpeg or symphonia, that falls back to ffmpeg)",
+        meta = "D"
+    )]
+    pub decoder: Decoder,
+
+    /// Tagging backend
+    #[options(help = "Tagging backend (taglib or lofty)", meta = "T")]
+    pub tagger: Tagger,
+
+    /// Decoder diagnostics
+    #[options(
+        help = "Print decoder diagnostics up to LEVEL (off, error, warn, info, debug or trace)",
+        meta = "LEVEL"
+    )]
+    pub diagnostics: Verbosity,
 }
 
 impl Opts {
@@ -253,18 +717,30 @@ impl Opts {
     }
 
     pub const fn do_dr(&self) -> bool {
-        self.dr
+        self.dr || self.dr_log
+    }
+
+    pub const fn show_diff(&self) -> bool {
+        self.diff || self.dry_run
+    }
+
+    pub fn target(&self) -> Target {
+        self.target
+            .unwrap_or_else(|| Target::from_pregain(self.pregain))
     }
 }
 
 pub fn version() -> ! {
     println!("MXC version {VERSION} - using:");
+    #[cfg(feature = "taglib")]
     println!(
         "\tlibtag {}.{}.{}",
         mxc::version::tag_version_major(),
         mxc::version::tag_version_minor(),
         mxc::version::tag_version_patch()
     );
+    #[cfg(not(feature = "taglib"))]
+    println!("\tlofty");
     println!("\tebur128 v0.1.6 based on libebur128 1.2.6");
     let lavf_ver = mxc::version::libavformat_version();
     println!(
@@ -3,31 +3,204 @@ use std::io::Write as IoWrite;
 
 use console::style;
 use indicatif::ProgressStyle;
+use mxc::compliance::Preset;
+use mxc::replay_gain::{ReplayGain, StoredRg};
 use mxc::walker::RGE;
 use mxc::AudioFile;
 
-pub fn pp_report(rge: &RGE, afs: &[AudioFile]) -> String {
+use crate::options::Opts;
+
+/// `old → new` of one value, flagged if gain changed by more than `threshold`
+fn pp_change(
+    name: &str,
+    old: Option<f64>,
+    new: Option<f64>,
+    unit: &str,
+    threshold: Option<f64>,
+) -> String {
+    let fmt = |x: Option<f64>| match x {
+        Some(x) => format!("{x:.2}{unit}"),
+        None => "none".to_string(),
+    };
+    let line = format!("{name}: {} → {}", fmt(old), fmt(new));
+    let flagged = match (old, new, threshold) {
+        (Some(old), Some(new), Some(t)) => (new - old).abs() > t,
+        _ => false,
+    };
+    if flagged {
+        format!("{}\n", style(line).yellow())
+    } else {
+        line + "\n"
+    }
+}
+
+/// Stored ReplayGain tags compared with new values (`None` when tags are deleted)
+pub fn pp_rg_diff(
+    stored: &StoredRg,
+    track_rg: Option<&ReplayGain>,
+    album_rg: Option<&ReplayGain>,
+    threshold: f64,
+) -> String {
+    [
+        pp_change(
+            "Track gain",
+            stored.track_gain,
+            track_rg.map(|rg| rg.gain),
+            " dB",
+            Some(threshold),
+        ),
+        pp_change(
+            "Track peak",
+            stored.track_peak,
+            track_rg.map(|rg| rg.peak),
+            "",
+            None,
+        ),
+        pp_change(
+            "Album gain",
+            stored.album_gain,
+            album_rg.map(|rg| rg.gain),
+            " dB",
+            Some(threshold),
+        ),
+        pp_change(
+            "Album peak",
+            stored.album_peak,
+            album_rg.map(|rg| rg.peak),
+            "",
+            None,
+        ),
+    ]
+    .concat()
+}
+
+/// Results of one file
+fn pp_file(rge: &RGE, af: &AudioFile, opts: &Opts) -> String {
+    let mut s = if rge.is_album() {
+        af.file.display().to_string() + "\n"
+    } else {
+        // on single this will prevent double line printing
+        String::new()
+    };
+    writeln!(s, "Stream: {}", af.info()).unwrap();
+    // we know this two will never panic
+    if let Some(track_rg) = af.track_rg {
+        writeln!(s, "{track_rg}").unwrap();
+    }
+    if let Some(dr_score) = af.dr_score {
+        writeln!(s, "DR14 Score: {dr_score}").unwrap();
+    }
+    if let Some(stored) = af.stored_rg.as_ref() {
+        s += &pp_rg_diff(
+            stored,
+            af.track_rg.as_ref(),
+            af.album_rg.as_ref(),
+            opts.diff_threshold,
+        );
+    }
+    if let Some(stereo) = af.stereo() {
+        if stereo.mono || stereo.polarity_inverted {
+            writeln!(s, "Stereo: {}", style(stereo).yellow()).unwrap();
+        } else {
+            writeln!(s, "Stereo: {stereo}").unwrap();
+        }
+    }
+    if let Some(clipping) = af.clipping() {
+        if clipping.is_clipped() {
+            writeln!(s, "Clipping: {}", style(clipping).yellow()).unwrap();
+        } else {
+            writeln!(s, "Clipping: {clipping}").unwrap();
+        }
+    }
+    if let Some(silence) = af.silence() {
+        if silence.silent {
+            writeln!(s, "Silence: {}", style(silence).yellow()).unwrap();
+        } else {
+            writeln!(s, "Silence: {silence}").unwrap();
+        }
+    }
+    if let Some(resolution) = af.resolution() {
+        if resolution.is_padded_or_upsampled() {
+            writeln!(s, "Resolution: {}", style(resolution).yellow()).unwrap();
+        } else {
+            writeln!(s, "Resolution: {resolution}").unwrap();
+        }
+    }
+    if let Some(spectrum) = af.spectrum().filter(|_| opts.spectrum) {
+        if spectrum.is_likely_transcoded() {
+            writeln!(s, "Spectrum: {}", style(spectrum).yellow()).unwrap();
+        } else {
+            writeln!(s, "Spectrum: {spectrum}").unwrap();
+        }
+    }
+    if af.corruption().is_damaged() {
+        writeln!(s, "{}", style(af.corruption()).red()).unwrap();
+    }
+    if af.duration_mismatch() {
+        writeln!(
+            s,
+            "{}",
+            style(format!(
+                "Duration mismatch: decoded {:.3}s, declared {:.3}s",
+                af.duration().unwrap_or_default(),
+                af.declared_duration().unwrap_or_default()
+            ))
+            .yellow()
+        )
+        .unwrap();
+    }
+    for diagnostic in af
+        .diagnostics()
+        .iter()
+        .filter(|d| d.level <= opts.diagnostics.0)
+    {
+        writeln!(s, "{}", style(diagnostic).dim()).unwrap();
+    }
+    s
+}
+
+/// Silence between consecutive tracks of album
+fn pp_gaps(afs: &[AudioFile]) -> Option<String> {
+    let silences = afs
+        .iter()
+        .map(|af| af.silence())
+        .collect::<Option<Vec<_>>>()?;
+    Some(
+        silences
+            .windows(2)
+            .enumerate()
+            .map(|(i, w)| format!("{}→{} {:.2}s", i + 1, i + 2, w[0].gap_to(w[1])))
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+/// Damaged files are listed separately (after healthy ones)
+pub fn pp_report(rge: &RGE, afs: &[AudioFile], opts: &Opts) -> String {
+    let (healthy, damaged): (Vec<_>, Vec<_>) = afs.iter().partition(|af| !af.is_damaged());
     format!(
-        "[{}] {rge}\n{}{}",
-        style('✔').green().bold(),
-        afs.iter()
-            .map(|af| {
-                let mut s = if rge.is_album() {
-                    af.file.display().to_string() + "\n"
-                } else {
-                    // on single this will prevent double line printing
-                    String::new()
-                };
-                // we know this two will never panic
-                if let Some(track_rg) = af.track_rg {
-                    writeln!(s, "{track_rg}").unwrap();
-                }
-                if let Some(dr_score) = af.dr_score {
-                    writeln!(s, "DR14 Score: {dr_score}").unwrap();
-                }
-                s
-            })
+        "[{}] {rge}\n{}{}{}",
+        if damaged.is_empty() {
+            style('✔').green().bold()
+        } else {
+            style('!').yellow().bold()
+        },
+        healthy
+            .iter()
+            .map(|af| pp_file(rge, af, opts))
             .collect::<String>(),
+        if damaged.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{}\n{}",
+                style("Damaged:").red().bold(),
+                damaged
+                    .iter()
+                    .map(|af| pp_file(rge, af, opts))
+                    .collect::<String>()
+            )
+        },
         if rge.is_album() {
             let mut s = rge.to_string() + "\n";
             // we know this two will never panic
@@ -37,6 +210,9 @@ pub fn pp_report(rge: &RGE, afs: &[AudioFile]) -> String {
             if let Some(dr_score) = afs[0].album_dr_score {
                 writeln!(s, "DR14 Score: {dr_score}").unwrap();
             }
+            if let Some(gaps) = pp_gaps(afs) {
+                writeln!(s, "Gaps between tracks: {gaps}").unwrap();
+            }
             s
         } else {
             String::new()
@@ -44,6 +220,52 @@ pub fn pp_report(rge: &RGE, afs: &[AudioFile]) -> String {
     )
 }
 
+/// Compliance of `rg` with every preset
+fn pp_compliance_of(rg: &ReplayGain, presets: &[Preset]) -> String {
+    let mut s = String::new();
+    for preset in presets {
+        let compliance = preset.check(rg);
+        if compliance.passes() {
+            writeln!(s, "{compliance}").unwrap();
+        } else {
+            writeln!(s, "{}", style(compliance).red()).unwrap();
+        }
+    }
+    s
+}
+
+/// Compliance of tracks and album (marked as failed if any check fails)
+pub fn pp_compliance(rge: &RGE, afs: &[AudioFile], presets: &[Preset]) -> String {
+    let rgs = afs
+        .iter()
+        .filter_map(|af| af.track_rg.as_ref())
+        .chain(afs[0].album_rg.as_ref());
+    let passes = rgs
+        .flat_map(|rg| presets.iter().map(|p| p.check(rg).passes()))
+        .all(|x| x);
+    let mut s = format!(
+        "[{}] {rge}\n",
+        if passes {
+            style('✔').green().bold()
+        } else {
+            style('x').red().bold()
+        }
+    );
+    for af in afs {
+        if rge.is_album() {
+            writeln!(s, "{}", af.file.display()).unwrap();
+        }
+        if let Some(track_rg) = af.track_rg.as_ref() {
+            s += &pp_compliance_of(track_rg, presets);
+        }
+    }
+    if let Some(album_rg) = afs[0].album_rg.as_ref() {
+        writeln!(s, "{rge}").unwrap();
+        s += &pp_compliance_of(album_rg, presets);
+    }
+    s
+}
+
 // from dano
 pub fn print_err<S: AsRef<str>>(err_buf: S) {
     // mutex keeps threads from writing over each other
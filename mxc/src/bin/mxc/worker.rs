@@ -1,41 +1,230 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
 use console::style;
 use indicatif::{ProgressBar, ProgressIterator};
-use mxc::replay_gain::album_rg;
+use mxc::analyzers::{
+    Clipping, Levels, LoudnessCurve, Resolution, Silence, Spectrogram, Spectrum, Stereo, Waveform,
+};
+use mxc::dr_log::{write_dr_log, DR_LOG};
+use mxc::journal::{Journal, JournalEntry};
+use mxc::replay_gain::{album_rg, Target};
 use mxc::walker::RGE;
-use mxc::{AudioFile, Error, MetaError};
+use mxc::{AudioFile, Error};
+
+use crate::options::{
+    ComplianceOpts, CurveFormat, DeleteOpts, Opts, Output, SpectrogramOpts, UndoOpts,
+    WaveformFormat,
+};
+use crate::printe::{
+    post_album_style, post_single_style, pp_compliance, pp_report, pp_rg_diff, print_err, print_out,
+};
+
+/// Path of sidecar file for `file` (next to it or in `dir`)
+fn sidecar(file: &Path, dir: Option<&Path>, extension: &str) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    match dir {
+        Some(dir) => dir.join(name),
+        None => file.with_file_name(name),
+    }
+}
 
-use crate::options::{DeleteOpts, Opts, Output};
-use crate::printe::{post_album_style, post_single_style, pp_report, print_err, print_out};
+/// Creates sidecar file for `af` and lets `f` fill it
+fn write_sidecar(
+    af: &AudioFile,
+    dir: Option<&Path>,
+    extension: &str,
+    f: impl FnOnce(BufWriter<File>) -> std::io::Result<()>,
+) -> Result<(), Error> {
+    let file = File::create(sidecar(&af.file, dir, extension))?;
+    Ok(f(BufWriter::new(file))?)
+}
+
+/// foo_dr log goes into album folder (or `dir`)
+fn dr_log_path(rge: &RGE, dir: Option<&Path>) -> PathBuf {
+    match rge {
+        RGE::Album(_) => {
+            let folder = rge.relevant_path();
+            match dir {
+                Some(dir) => {
+                    let name = folder.file_name().unwrap_or_default().to_string_lossy();
+                    dir.join(format!("{name} - {DR_LOG}"))
+                }
+                None => folder.join(DR_LOG),
+            }
+        }
+        RGE::Single(file) => sidecar(file, dir, DR_LOG),
+    }
+}
 
-pub fn delete_on_rge(rge: &RGE, opts: &DeleteOpts) -> Result<(), Error> {
+pub fn delete_on_rge(rge: &RGE, opts: &DeleteOpts, journal: Option<&Journal>) -> Result<(), Error> {
     let mut files = rge
         .sliced()
         .iter()
-        .map(AudioFile::new)
+        .map(|p| AudioFile::with_backends(p, opts.decoder, opts.tagger))
         .collect::<Result<Vec<AudioFile>, _>>()?;
+    for audio_file in &mut files {
+        audio_file.safe_write = opts.safe_write;
+    }
+
+    if opts.dry_run {
+        let mut s = format!("[{}] {rge}\n", style('-').yellow().bold());
+        for audio_file in &mut files {
+            audio_file.read_stored_rg()?;
+            if rge.is_album() {
+                writeln!(s, "{}", audio_file.file.display()).unwrap();
+            }
+            if let Some(stored) = audio_file.stored_rg.as_ref() {
+                s += &pp_rg_diff(stored, None, None, 0.0);
+            }
+        }
+        print_out(s);
+        return Ok(());
+    }
 
+    let dr_tags = [opts.dr_tag.as_str(), opts.album_dr_tag.as_str()];
+    let extra: &[&str] = if opts.dr { &dr_tags } else { &[] };
     for audio_file in &mut files {
-        audio_file.delete_tags(opts.strip_uncommon_tags, opts.id3v2version)?
+        if let Some(journal) = journal {
+            journal.record(&audio_file.journal_entry(extra)?)?;
+        }
+        audio_file.delete_tags(opts.strip_uncommon_tags, opts.id3v2version)?;
+        if opts.dr {
+            audio_file.delete_dr_tags(opts.id3v2version, &opts.dr_tag, &opts.album_dr_tag)?;
+        }
     }
 
     Ok(())
 }
 
+/// Restores tags of one file recorded in journal
+pub fn undo_entry(entry: &JournalEntry, opts: &UndoOpts) -> Result<(), Error> {
+    let mut audio_file = AudioFile::with_backends(&entry.file, opts.decoder, opts.tagger)?;
+    audio_file.safe_write = opts.safe_write;
+    audio_file.restore_tags(opts.id3v2version, entry)?;
+    Ok(())
+}
+
+/// Checks loudness compliance of RGE unit and prints it
+pub fn compliance_on_rge(
+    rge: &RGE,
+    opts: &ComplianceOpts,
+    pb: Option<ProgressBar>,
+) -> Result<(), Error> {
+    let result = (|| -> Result<Vec<AudioFile>, Error> {
+        let mut files = rge
+            .sliced()
+            .iter()
+            .map(|p| AudioFile::with_backends(p, opts.decoder, opts.tagger))
+            .collect::<Result<Vec<AudioFile>, _>>()?;
+        for af in files.iter_mut() {
+            if let Some(p) = pb.as_ref() {
+                p.set_length(af.len);
+            }
+            let progress = pb.as_ref().map(|p| |pos: u64| p.set_position(pos));
+            af.seed(true, false, opts.allow_corrupted, progress)?;
+            // loudness and peak do not depend on target
+            af.track_gain(Target::default(), true)?;
+        }
+        if rge.is_album() && !opts.no_album {
+            let album_rg = album_rg(&files, Target::default())?;
+            for af in files.iter_mut() {
+                af.album_rg = Some(album_rg);
+            }
+        }
+        Ok(files)
+    })();
+    let msg = match &result {
+        Ok(files) => pp_compliance(rge, files, &opts.presets()),
+        Err(err) => format!(
+            "[{}] {rge} {}\n",
+            style('x').red().bold(),
+            style(err.to_string()).red()
+        ),
+    };
+    match (opts.output, pb.as_ref()) {
+        (Output::Tui, Some(p)) => p.println(msg.trim_end()),
+        _ if result.is_ok() => print_out(msg),
+        _ => print_err(msg),
+    }
+    if let Some(p) = pb.as_ref() {
+        p.finish_and_clear();
+    }
+    result.map(|_| ())
+}
+
+/// Renders spectrogram of every file in RGE unit
+pub fn spectrogram_on_rge(
+    rge: &RGE,
+    opts: &SpectrogramOpts,
+    pb: Option<ProgressBar>,
+) -> Result<(), Error> {
+    let result = rge
+        .sliced()
+        .iter()
+        .try_for_each(|path| -> Result<(), Error> {
+            let mut af = AudioFile::with_backends(path, opts.decoder, opts.tagger)?;
+            let spectrogram = Spectrogram::new(af.info())
+                .with_fft_size(opts.fft_size)
+                .with_scale(opts.scale)
+                .with_size(opts.width, opts.height);
+            af.add_analyzer(spectrogram);
+            if let Some(p) = pb.as_ref() {
+                p.set_length(af.len);
+                p.set_message(format!("{}", af.file.display()));
+            }
+            let progress = pb.as_ref().map(|p| |pos: u64| p.set_position(pos));
+            af.seed(false, false, opts.allow_corrupted, progress)?;
+            let spectrogram = af.analyzer::<Spectrogram>().unwrap();
+            write_sidecar(&af, opts.sidecar_dir.as_deref(), "spectrogram.png", |w| {
+                spectrogram.write_png(w)
+            })
+        });
+    if let Err(err) = &result {
+        let msg = format!(
+            "[{}] {rge} {}",
+            style('x').red().bold(),
+            style(err.to_string()).red()
+        );
+        match (opts.output, pb.as_ref()) {
+            (Output::Tui, Some(p)) => p.println(msg),
+            _ => print_err(msg),
+        }
+    }
+    if let Some(p) = pb.as_ref() {
+        p.finish_and_clear();
+    }
+    result
+}
+
 /// This function "mach" (eng. does) one RGE unit.
 /// Whatever that means in the context of RGE.
-pub fn mach_rge(rge: &RGE, write: bool, opts: &Opts, pb: Option<ProgressBar>) -> Result<(), Error> {
-    match mach_rge_for_real(rge, &pb, opts, write) {
+///
+/// Pipelined will decode and analyze each file on multiple threads.
+pub fn mach_rge(
+    rge: &RGE,
+    write: bool,
+    pipelined: bool,
+    opts: &Opts,
+    journal: Option<&Journal>,
+    pb: Option<ProgressBar>,
+) -> Result<(), Error> {
+    match mach_rge_for_real(rge, &pb, opts, write, pipelined, journal) {
         Ok(files) => {
             match opts.output {
                 Output::Tui => {
                     if let Some(p) = pb.as_ref() {
-                        p.println(pp_report(rge, &files));
+                        p.println(pp_report(rge, &files, opts));
                         p.finish_and_clear();
                     } else {
                         panic!("TUI not working!!!")
                     }
                 }
-                Output::PrettyPrint => print_out(&pp_report(rge, &files)),
+                Output::PrettyPrint => print_out(&pp_report(rge, &files, opts)),
                 Output::Log => todo!(),
             };
             Ok(())
@@ -72,13 +261,63 @@ fn mach_rge_for_real(
     pb: &Option<ProgressBar>,
     opts: &Opts,
     write: bool,
+    pipelined: bool,
+    journal: Option<&Journal>,
 ) -> Result<Vec<AudioFile>, Error> {
     let mut files = rge
         .sliced()
         .iter()
-        .map(AudioFile::new)
+        .map(|p| AudioFile::with_backends(p, opts.decoder, opts.tagger))
         .collect::<Result<Vec<AudioFile>, _>>()?;
 
+    for af in files.iter_mut() {
+        af.safe_write = opts.safe_write;
+    }
+
+    // remember old tags before they get overwritten
+    if opts.show_diff() {
+        files.iter_mut().try_for_each(|af| af.read_stored_rg())?;
+    }
+
+    // register additional analyzers
+    for af in files.iter_mut() {
+        // resolution analyzer also provides spectrum
+        if opts.spectrum && !opts.resolution {
+            let spectrum = Spectrum::new(af.info());
+            af.add_analyzer(spectrum);
+        }
+        if opts.clipping {
+            let clipping = Clipping::new(af.info());
+            af.add_analyzer(clipping);
+        }
+        if opts.silence {
+            let silence = Silence::new(af.info()).with_threshold(opts.silence_threshold);
+            af.add_analyzer(silence);
+        }
+        if opts.stereo {
+            let stereo = Stereo::new(af.info())?;
+            af.add_analyzer(stereo);
+        }
+        if opts.resolution {
+            let resolution = Resolution::new(af.info());
+            af.add_analyzer(resolution);
+        }
+        if opts.dr_log {
+            let levels = Levels::new(af.info());
+            af.add_analyzer(levels);
+        }
+        if opts.loudness_curve.is_some() {
+            let curve = LoudnessCurve::new(af.info(), opts.curve_interval)?;
+            af.add_analyzer(curve);
+        }
+        if opts.waveform.is_some() {
+            let waveform = Waveform::new(af.info())
+                .with_zoom(opts.waveform_zoom)
+                .with_bits(opts.waveform_bits);
+            af.add_analyzer(waveform);
+        }
+    }
+
     // this is used for progress
     let len = if rge.is_album() {
         Some(files.len())
@@ -99,25 +338,42 @@ fn mach_rge_for_real(
                 )
             }
         }
-        af.seed(
-            opts.do_rg(),
-            opts.do_dr(),
-            opts.allow_corrupted,
-            pb.as_ref().map(|p| |pos: u64| p.set_position(pos)),
-        )
+        let progress = pb.as_ref().map(|p| |pos: u64| p.set_position(pos));
+        if pipelined {
+            af.seed_pipelined(opts.do_rg(), opts.do_dr(), opts.allow_corrupted, progress)
+        } else {
+            af.seed(opts.do_rg(), opts.do_dr(), opts.allow_corrupted, progress)
+        }
     })?;
 
+    // export sidecar files
+    let dir = opts.sidecar_dir.as_deref();
+    for af in files.iter() {
+        if let (Some(format), Some(curve)) = (opts.loudness_curve, af.analyzer::<LoudnessCurve>()) {
+            write_sidecar(af, dir, format.extension(), |w| match format {
+                CurveFormat::Csv => curve.write_csv(w),
+                CurveFormat::Json => curve.write_json(w),
+            })?;
+        }
+        if let (Some(format), Some(waveform)) = (opts.waveform, af.analyzer::<Waveform>()) {
+            write_sidecar(af, dir, format.extension(), |w| match format {
+                WaveformFormat::Dat => waveform.write_dat(w),
+                WaveformFormat::Json => waveform.write_json(w),
+            })?;
+        }
+    }
+
     // do RG
     if opts.do_rg() {
         files
             .iter_mut()
-            .try_for_each(|af| af.track_gain(opts.pregain, opts.non_standard_opus))?;
+            .try_for_each(|af| af.track_gain(opts.target(), opts.non_standard_opus))?;
     }
 
     // do album RG
     let album_rg = if opts.do_album() && opts.do_rg() {
         Some(
-            album_rg(&files, opts.pregain)?
+            album_rg(&files, opts.target())?
                 .clipper(opts.maxtpl, !opts.no_clip_prevention)
                 .0,
         )
@@ -134,6 +390,18 @@ fn mach_rge_for_real(
         None
     };
 
+    // write foo_dr log
+    if opts.dr_log {
+        // album folder or file name, if there are no artist and album tags
+        let fallback = match rge {
+            RGE::Album(_) => rge.relevant_path().file_name(),
+            RGE::Single(file) => file.file_stem(),
+        };
+        let fallback = fallback.unwrap_or_default().to_string_lossy();
+        let file = File::create(dr_log_path(rge, opts.sidecar_dir.as_deref()))?;
+        write_dr_log(BufWriter::new(file), &fallback, &files, album_dr)?;
+    }
+
     // post-seeding progress bar
     if let Some(p) = pb.as_ref() {
         p.set_length(files.len() as u64);
@@ -152,7 +420,7 @@ fn mach_rge_for_real(
         .iter_mut()
         // in case we do not use tui we just use hidden progressbar that does nothing
         .progress_with(pb.clone().unwrap_or_else(ProgressBar::hidden))
-        .try_for_each(|audio_file| -> Result<(), MetaError> {
+        .try_for_each(|audio_file| -> Result<(), Error> {
             // check clipping and maybe prevent it
             if let Some(track_rg) = audio_file.track_rg.as_mut() {
                 track_rg.clipper(opts.maxtpl, !opts.no_clip_prevention);
@@ -163,8 +431,17 @@ fn mach_rge_for_real(
                 audio_file.album_dr_score = album_dr;
             }
 
+            // remember old tags, so they can be restored
+            if write && (opts.do_rg() || opts.do_dr()) {
+                if let Some(journal) = journal {
+                    let dr_tags = [opts.dr_tag.as_str(), opts.album_dr_tag.as_str()];
+                    let extra: &[&str] = if opts.do_dr() { &dr_tags } else { &[] };
+                    journal.record(&audio_file.journal_entry(extra)?)?;
+                }
+            }
+
             // write tags if requested
-            if write {
+            if write && opts.do_rg() {
                 audio_file.write_tags(
                     opts.strip_uncommon_tags,
                     opts.id3v2version,
@@ -172,10 +449,12 @@ fn mach_rge_for_real(
                     "dB",
                     opts.lowercase_tags,
                     opts.non_standard_opus,
-                )
-            } else {
-                Ok(())
+                )?;
             }
+            if write && opts.do_dr() {
+                audio_file.write_dr_tags(opts.id3v2version, &opts.dr_tag, &opts.album_dr_tag)?;
+            }
+            Ok(())
         })?;
 
     Ok(files)
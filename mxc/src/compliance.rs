@@ -0,0 +1,227 @@
+//! Loudness compliance of broadcast standards and streaming platforms
+
+use std::fmt::Display;
+
+use crate::replay_gain::{linear_to_db, ReplayGain};
+
+/// What platform does with tracks that are quieter than its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositiveGain {
+    /// Never turns up (only down)
+    Never,
+    /// Turns up only until true peak reaches ceiling
+    PeakLimited,
+    /// Turns up fully (limiter or delivery spec takes care of peaks)
+    Full,
+}
+
+/// Loudness target of standard or platform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    /// Short name used on command line
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Integrated loudness in LUFS
+    pub target: f64,
+    /// Allowed deviation from target in LU
+    pub tolerance: f64,
+    /// Max true peak in dBTP
+    pub max_true_peak: f64,
+    pub positive_gain: PositiveGain,
+}
+
+/// Known presets (broadcast standards first)
+pub const PRESETS: [Preset; 6] = [
+    Preset {
+        id: "ebu",
+        name: "EBU R128",
+        target: -23.0,
+        tolerance: 0.5,
+        max_true_peak: -1.0,
+        positive_gain: PositiveGain::Full,
+    },
+    Preset {
+        id: "atsc",
+        name: "ATSC A/85",
+        target: -24.0,
+        tolerance: 2.0,
+        max_true_peak: -2.0,
+        positive_gain: PositiveGain::Full,
+    },
+    Preset {
+        id: "spotify",
+        name: "Spotify",
+        target: -14.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+        positive_gain: PositiveGain::PeakLimited,
+    },
+    Preset {
+        id: "apple",
+        name: "Apple Music",
+        target: -16.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+        positive_gain: PositiveGain::PeakLimited,
+    },
+    Preset {
+        id: "youtube",
+        name: "YouTube",
+        target: -14.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+        positive_gain: PositiveGain::Never,
+    },
+    Preset {
+        id: "tidal",
+        name: "Tidal",
+        target: -14.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+        positive_gain: PositiveGain::Never,
+    },
+];
+
+/// Finds preset by its id (case insensitive)
+pub fn preset(id: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.id.eq_ignore_ascii_case(id))
+}
+
+impl std::str::FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        preset(s).copied().ok_or_else(|| {
+            let ids: Vec<_> = PRESETS.iter().map(|p| p.id).collect();
+            format!(
+                "unknown preset `{s}`; only {} are supported",
+                ids.join(", ")
+            )
+        })
+    }
+}
+
+impl Preset {
+    /// Checks loudness and true peak from [ReplayGain] (track or album)
+    pub fn check(&self, rg: &ReplayGain) -> Compliance {
+        let true_peak = linear_to_db(rg.peak);
+        let gain = self.target - rg.loudness;
+        let playback_gain = if gain <= 0.0 {
+            gain
+        } else {
+            match self.positive_gain {
+                PositiveGain::Never => 0.0,
+                PositiveGain::PeakLimited => gain.min(self.max_true_peak - true_peak).max(0.0),
+                PositiveGain::Full => gain,
+            }
+        };
+        Compliance {
+            preset: *self,
+            loudness: rg.loudness,
+            true_peak,
+            loudness_ok: gain.abs() <= self.tolerance,
+            true_peak_ok: true_peak <= self.max_true_peak,
+            playback_gain,
+        }
+    }
+}
+
+/// Result of [Preset::check]
+#[derive(Debug, Clone, Copy)]
+pub struct Compliance {
+    pub preset: Preset,
+    /// Integrated loudness in LUFS
+    pub loudness: f64,
+    /// True peak in dBTP
+    pub true_peak: f64,
+    pub loudness_ok: bool,
+    pub true_peak_ok: bool,
+    /// Gain (dB) that platform applies on playback
+    pub playback_gain: f64,
+}
+
+impl Compliance {
+    pub fn passes(&self) -> bool {
+        self.loudness_ok && self.true_peak_ok
+    }
+}
+
+impl Display for Compliance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<12} {} {:7.2} LUFS ({:+.1} LUFS ±{}) {:6.2} dBTP (max {:+.1}) playback gain {:+.2} dB",
+            self.preset.name,
+            if self.passes() { "pass" } else { "FAIL" },
+            self.loudness,
+            self.preset.target,
+            self.preset.tolerance,
+            self.true_peak,
+            self.preset.max_true_peak,
+            self.playback_gain
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_gain::db_to_linear;
+
+    /// Track with loudness (LUFS) and true peak (dBTP)
+    fn rg(loudness: f64, true_peak: f64) -> ReplayGain {
+        ReplayGain {
+            loudness,
+            peak: db_to_linear(true_peak),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn preset_from_str() {
+        assert_eq!("EBU".parse::<Preset>().unwrap().target, -23.0);
+        assert_eq!("spotify".parse::<Preset>().unwrap().name, "Spotify");
+        assert!("deezer".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn presets() {
+        // (preset, loudness, true peak, loudness_ok, true_peak_ok, playback gain)
+        let table = [
+            // EBU R128: ±0.5 LU, -1 dBTP
+            ("ebu", -23.0, -1.1, true, true, 0.0),
+            ("ebu", -23.5, -3.0, true, true, 0.5),
+            ("ebu", -22.4, -3.0, false, true, -0.6),
+            ("ebu", -23.6, -3.0, false, true, 0.6),
+            ("ebu", -23.0, -0.9, true, false, 0.0),
+            // ATSC A/85: ±2 LU, -2 dBTP
+            ("atsc", -22.0, -2.1, true, true, -2.0),
+            ("atsc", -26.5, -1.5, false, false, 2.5),
+            // Spotify: turns up only until limit of -1 dBTP
+            ("spotify", -14.0, -1.1, true, true, 0.0),
+            ("spotify", -9.0, 0.0, false, false, -5.0),
+            ("spotify", -20.0, -10.0, false, true, 6.0),
+            ("spotify", -20.0, -3.0, false, true, 2.0),
+            ("spotify", -20.0, 0.5, false, false, 0.0),
+            // Apple Music: no positive gain over peak limit
+            ("apple", -16.0, -1.1, true, true, 0.0),
+            ("apple", -18.0, -1.5, false, true, 0.5),
+            ("apple", -18.0, -0.5, false, false, 0.0),
+            ("apple", -12.0, -0.1, false, false, -4.0),
+            // YouTube and Tidal: only turn down
+            ("youtube", -20.0, -10.0, false, true, 0.0),
+            ("youtube", -8.0, -0.5, false, false, -6.0),
+            ("tidal", -14.5, -1.1, true, true, 0.0),
+            ("tidal", -10.0, -1.1, false, true, -4.0),
+        ];
+        for (id, loudness, true_peak, loudness_ok, true_peak_ok, playback_gain) in table {
+            let c = preset(id).unwrap().check(&rg(loudness, true_peak));
+            let case = format!("{id} {loudness} LUFS {true_peak} dBTP");
+            assert_eq!(c.loudness_ok, loudness_ok, "{case}");
+            assert_eq!(c.true_peak_ok, true_peak_ok, "{case}");
+            assert_eq!(c.passes(), loudness_ok && true_peak_ok, "{case}");
+            assert!((c.true_peak - true_peak).abs() < 1e-9, "{case}");
+            assert!((c.playback_gain - playback_gain).abs() < 1e-9, "{case}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::fmt::Display;
+use std::os::raw::{c_char, c_int, c_void};
+
+use ffmpeg::ffi;
+use ffmpeg_next as ffmpeg;
+use log::Level;
+
+/// Message that decoder emitted while working on file.
+///
+/// Those are usually hints that file is damaged (like "invalid frame header" or "overread").
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// Component that emitted message (like `mp3float`)
+    pub component: Option<String>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.component {
+            Some(c) => write!(f, "[{}] {c}: {}", self.level, self.message),
+            None => write!(f, "[{}] {}", self.level, self.message),
+        }
+    }
+}
+
+/// Maps FFmpeg log level to [Level]
+fn level(level: c_int) -> Level {
+    match level {
+        l if l <= ffi::AV_LOG_ERROR => Level::Error,
+        l if l <= ffi::AV_LOG_WARNING => Level::Warn,
+        l if l <= ffi::AV_LOG_INFO => Level::Info,
+        l if l <= ffi::AV_LOG_VERBOSE => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+type VaList = *mut ffi::__va_list_tag;
+#[cfg(not(all(target_arch = "x86_64", not(windows))))]
+type VaList = ffi::va_list;
+
+/// Messages that are collected on this thread
+#[derive(Default)]
+struct Capture {
+    /// FFmpeg can send line in multiple parts
+    partial: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Capture>> = RefCell::new(None);
+}
+
+/// Runs `f` and collects all FFmpeg messages that were emitted (on this thread) while it was running
+pub(crate) fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    // allow nesting
+    let previous = CAPTURE.with(|c| c.borrow_mut().replace(Capture::default()));
+    let r = f();
+    let captured = CAPTURE.with(|c| std::mem::replace(&mut *c.borrow_mut(), previous));
+    (r, captured.map(|c| c.diagnostics).unwrap_or_default())
+}
+
+/// Routes FFmpeg messages to [capture] or to [log] if nobody is capturing
+pub(crate) fn install() {
+    unsafe {
+        ffi::av_log_set_level(ffi::AV_LOG_INFO);
+        ffi::av_log_set_callback(Some(callback));
+    }
+}
+
+unsafe extern "C" fn callback(avcl: *mut c_void, lvl: c_int, fmt: *const c_char, vl: VaList) {
+    if lvl > ffi::av_log_get_level() {
+        return;
+    }
+    let mut line = [0 as c_char; 1024];
+    // we get component on our own (prefix contains pointer address)
+    let mut print_prefix = 0;
+    ffi::av_log_format_line2(
+        avcl,
+        lvl,
+        fmt,
+        vl,
+        line.as_mut_ptr(),
+        line.len() as c_int,
+        &mut print_prefix,
+    );
+    let text = CStr::from_ptr(line.as_ptr()).to_string_lossy();
+
+    let component = if avcl.is_null() {
+        None
+    } else {
+        let class = *(avcl as *const *const ffi::AVClass);
+        class.as_ref().and_then(|c| c.item_name).map(|item_name| {
+            CStr::from_ptr(item_name(avcl))
+                .to_string_lossy()
+                .to_string()
+        })
+    };
+
+    CAPTURE.with(|c| match c.borrow_mut().as_mut() {
+        Some(capture) => {
+            capture.partial.push_str(&text);
+            if capture.partial.ends_with('\n') {
+                let message = std::mem::take(&mut capture.partial).trim().to_owned();
+                if !message.is_empty() {
+                    capture.diagnostics.push(Diagnostic {
+                        level: level(lvl),
+                        component,
+                        message,
+                    });
+                }
+            }
+        }
+        None => {
+            // these are not file related, so they are only interesting for debugging
+            log::debug!(target: "ffmpeg", "{}", text.trim_end());
+        }
+    });
+}
@@ -0,0 +1,272 @@
+//! foobar2000 compatible DR logs (`foo_dr.txt`)
+
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audiofile::{AudioFile, DRscore};
+use crate::seeders::AudioInfo;
+
+/// Name of log file in album folder
+pub const DR_LOG: &str = "foo_dr.txt";
+
+const LINE: &str =
+    "--------------------------------------------------------------------------------";
+const DOUBLE_LINE: &str =
+    "================================================================================";
+
+/// Current UTC time as `YYYY-MM-DD hh:mm:ss`
+fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    // days to civil date (proleptic Gregorian)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// `m:ss`
+fn duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Track name as foobar2000 shows it: `NN-Title` if there is title tag
+/// (track number falls back to position in log), file stem otherwise
+fn track_name(title: Option<&str>, number: Option<&str>, position: usize, stem: &str) -> String {
+    match title.filter(|t| !t.is_empty()) {
+        Some(title) => {
+            // "3/12" -> 3
+            let number = number
+                .and_then(|n| n.split('/').next()?.trim().parse().ok())
+                .unwrap_or(position);
+            format!("{number:02}-{title}")
+        }
+        None => stem.to_owned(),
+    }
+}
+
+/// `Artist / Album` as foo_dr shows it, `fallback` (folder or file name) if tags are missing
+fn analyzed_name(artist: Option<&str>, album: Option<&str>, fallback: &str) -> String {
+    match (
+        artist.filter(|a| !a.is_empty()),
+        album.filter(|a| !a.is_empty()),
+    ) {
+        (Some(artist), Some(album)) => format!("{artist} / {album}"),
+        _ => fallback.to_owned(),
+    }
+}
+
+/// One track line of log
+#[derive(Debug, Clone)]
+struct Track {
+    dr: DRscore,
+    /// dB
+    peak: f64,
+    /// dB
+    rms: f64,
+    /// seconds
+    duration: f64,
+    name: String,
+}
+
+impl Track {
+    fn new(af: &AudioFile, position: usize) -> Self {
+        let (peak, rms) = af
+            .levels()
+            .map(|l| (l.peak_db(), l.rms_db()))
+            .unwrap_or((f64::NAN, f64::NAN));
+        let tags = af.read_tags(&["TITLE", "TRACKNUMBER"]).unwrap_or_default();
+        let tag = |i: usize| tags.get(i).and_then(Option::as_deref);
+        let stem = af.file.file_stem().unwrap_or_default().to_string_lossy();
+        Self {
+            dr: af.dr_score.unwrap_or_default(),
+            peak,
+            rms,
+            duration: af.duration().unwrap_or_default(),
+            name: track_name(tag(0), tag(1), position, &stem),
+        }
+    }
+}
+
+/// Writes log of `files` (with DR scores and [Levels](crate::analyzers::Levels) results)
+/// in foo_dr format (CRLF line endings). Files without DR score are skipped.
+/// Artist and album are read from tags of first file, `fallback` is used if they are missing.
+pub fn write_dr_log<W: Write>(
+    w: W,
+    fallback: &str,
+    files: &[AudioFile],
+    album_dr: Option<DRscore>,
+) -> std::io::Result<()> {
+    let files: Vec<_> = files.iter().filter(|af| af.dr_score.is_some()).collect();
+    let tracks: Vec<_> = files
+        .iter()
+        .enumerate()
+        .map(|(i, af)| Track::new(af, i + 1))
+        .collect();
+    let tags = files
+        .first()
+        .and_then(|af| af.read_tags(&["ARTIST", "ALBUM"]).ok())
+        .unwrap_or_default();
+    let tag = |i: usize| tags.get(i).and_then(Option::as_deref);
+    write_log(
+        w,
+        &now(),
+        &analyzed_name(tag(0), tag(1), fallback),
+        &tracks,
+        album_dr,
+        files.first().map(|af| af.info()),
+    )
+}
+
+fn write_log<W: Write>(
+    mut w: W,
+    date: &str,
+    analyzed: &str,
+    tracks: &[Track],
+    album_dr: Option<DRscore>,
+    info: Option<&AudioInfo>,
+) -> std::io::Result<()> {
+    write!(w, "foo_dr 1.0.4 log file\r\n")?;
+    write!(w, "Log date: {date}\r\n\r\n")?;
+    write!(w, "{LINE}\r\nAnalyzed: {analyzed}\r\n{LINE}\r\n\r\n")?;
+    write!(
+        w,
+        "DR         Peak         RMS     Duration Track\r\n{LINE}\r\n"
+    )?;
+    for track in tracks {
+        write!(
+            w,
+            "DR{:<4}{:>9.2} dB{:>9.2} dB{:>10} {}\r\n",
+            track.dr,
+            track.peak,
+            track.rms,
+            duration(track.duration),
+            track.name
+        )?;
+    }
+    write!(w, "{LINE}\r\n\r\n")?;
+    write!(w, "Number of tracks:  {}\r\n", tracks.len())?;
+    match album_dr.or_else(|| tracks.first().map(|t| t.dr)) {
+        Some(dr) => write!(w, "Official DR value: DR{dr}\r\n\r\n")?,
+        None => write!(w, "Official DR value: ERR\r\n\r\n")?,
+    }
+    if let Some(info) = info {
+        write!(w, "Samplerate:        {} Hz\r\n", info.rate)?;
+        write!(w, "Channels:          {}\r\n", info.channels)?;
+        if let Some(bits) = info.bits_per_raw_sample.filter(|&b| b > 0) {
+            write!(w, "Bits per sample:   {bits}\r\n")?;
+        }
+        if let Some(bitrate) = info.bitrate {
+            write!(w, "Bitrate:           {} kbps\r\n", bitrate / 1000)?;
+        }
+        write!(w, "Codec:             {}\r\n", info.codec.to_uppercase())?;
+    }
+    write!(w, "{DOUBLE_LINE}\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_names() {
+        assert_eq!(
+            track_name(Some("Time"), Some("4"), 1, "04 - Time"),
+            "04-Time"
+        );
+        assert_eq!(track_name(Some("Time"), Some("4/10"), 1, "x"), "04-Time");
+        assert_eq!(track_name(Some("Time"), None, 7, "x"), "07-Time");
+        assert_eq!(track_name(Some("Time"), Some("A1"), 7, "x"), "07-Time");
+        // no double prefix from file name
+        assert_eq!(track_name(None, Some("4"), 1, "04 - Time"), "04 - Time");
+        assert_eq!(track_name(Some(""), None, 1, "04 - Time"), "04 - Time");
+    }
+
+    #[test]
+    fn analyzed_names() {
+        assert_eq!(
+            analyzed_name(Some("Pink Floyd"), Some("Meddle"), "1971 - Meddle"),
+            "Pink Floyd / Meddle"
+        );
+        assert_eq!(
+            analyzed_name(None, Some("Meddle"), "1971 - Meddle"),
+            "1971 - Meddle"
+        );
+        assert_eq!(analyzed_name(Some("Pink Floyd"), Some(""), "x"), "x");
+        assert_eq!(analyzed_name(None, None, "01 - Echoes"), "01 - Echoes");
+    }
+
+    #[test]
+    fn same_as_foobar2000() {
+        let track = |dr, peak, rms, duration, name: &str| Track {
+            dr,
+            peak,
+            rms,
+            duration,
+            name: name.to_owned(),
+        };
+        let tracks = [
+            track(12, -0.82, -18.53, 229.4, "01-Speak to Me / Breathe"),
+            track(13, -0.20, -18.89, 215.0, "02-On the Run"),
+            track(11, -0.04, -14.37, 2469.6, "03-Time"),
+        ];
+        let mut info = AudioInfo::for_test(44100, 2, "flac");
+        info.bits_per_raw_sample = Some(16);
+        info.bitrate = Some(1_010_512);
+        let mut log = Vec::new();
+        write_log(
+            &mut log,
+            "2012-03-15 16:34:51",
+            "Pink Floyd / The Dark Side of the Moon",
+            &tracks,
+            Some(12),
+            Some(&info),
+        )
+        .unwrap();
+        // foo_dr_meter 1.0.4 output
+        let expected = "\
+foo_dr 1.0.4 log file
+Log date: 2012-03-15 16:34:51
+
+--------------------------------------------------------------------------------
+Analyzed: Pink Floyd / The Dark Side of the Moon
+--------------------------------------------------------------------------------
+
+DR         Peak         RMS     Duration Track
+--------------------------------------------------------------------------------
+DR12      -0.82 dB   -18.53 dB      3:49 01-Speak to Me / Breathe
+DR13      -0.20 dB   -18.89 dB      3:35 02-On the Run
+DR11      -0.04 dB   -14.37 dB     41:10 03-Time
+--------------------------------------------------------------------------------
+
+Number of tracks:  3
+Official DR value: DR12
+
+Samplerate:        44100 Hz
+Channels:          2
+Bits per sample:   16
+Bitrate:           1010 kbps
+Codec:             FLAC
+================================================================================
+";
+        assert_eq!(
+            String::from_utf8(log).unwrap(),
+            expected.replace('\n', "\r\n")
+        );
+    }
+}
@@ -9,10 +9,19 @@ pub enum MetaError {
     Unsupported(String),
     #[error("RG is empty")]
     NotComputed,
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
     #[error("Internal error: {0}")]
     Internal(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
+#[cfg(feature = "lofty")]
+impl From<lofty::error::LoftyError> for MetaError {
+    fn from(x: lofty::error::LoftyError) -> Self {
+        Self::Internal(Box::new(x))
+    }
+}
+
 // seed
 #[derive(SuperError, Debug)]
 pub enum SeedError {
@@ -22,6 +31,8 @@ pub enum SeedError {
     Ebur(#[from] ebur128::Error),
     #[error("File has something not supported: {0}")]
     DRMeter(#[from] drmeter::Error),
+    #[error("File is damaged: {0}")]
+    Damaged(String),
     #[error("Internal error: {0}")]
     Internal(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
@@ -32,6 +43,13 @@ impl From<ffmpeg_next::Error> for SeedError {
     }
 }
 
+#[cfg(feature = "symphonia")]
+impl From<symphonia::core::errors::Error> for SeedError {
+    fn from(x: symphonia::core::errors::Error) -> Self {
+        Self::Internal(Box::new(x))
+    }
+}
+
 //
 #[derive(SuperError, Debug)]
 pub enum NError {
@@ -49,6 +67,17 @@ impl From<ffmpeg_next::Error> for NError {
     }
 }
 
+#[cfg(feature = "symphonia")]
+impl From<symphonia::core::errors::Error> for NError {
+    fn from(x: symphonia::core::errors::Error) -> Self {
+        match x {
+            symphonia::core::errors::Error::IoError(x) => Self::IO(x),
+            symphonia::core::errors::Error::Unsupported(x) => Self::Unsupported(x.to_owned()),
+            x => Self::Internal(Box::new(x)),
+        }
+    }
+}
+
 /// General errors
 #[derive(SuperError, Debug)]
 pub enum WalkerError {
@@ -73,6 +102,8 @@ pub enum Error {
     Internal(#[from] Box<dyn std::error::Error + Send + Sync>),
     #[error("File has something not supported: {0}")]
     Unsupported(String),
+    #[error("File is damaged: {0}")]
+    Damaged(String),
 }
 
 impl From<NError> for Error {
@@ -91,6 +122,7 @@ impl From<SeedError> for Error {
             SeedError::Unsupported(x) => Self::Unsupported(x),
             SeedError::Ebur(x) => Self::Ebur(x),
             SeedError::DRMeter(x) => Self::Dr(x),
+            SeedError::Damaged(x) => Self::Damaged(x),
             SeedError::Internal(x) => Self::Internal(x),
         }
     }
@@ -102,6 +134,7 @@ impl From<MetaError> for Error {
             MetaError::Write(_) => Self::Internal(me.into()),
             MetaError::Unsupported(x) => Self::Unsupported(x),
             MetaError::NotComputed => Self::NotComputed,
+            MetaError::IO(x) => Self::IO(x),
             MetaError::Internal(x) => Self::Internal(x),
         }
     }
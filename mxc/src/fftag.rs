@@ -1,16 +1,17 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use ffmpeg::format::sample::Type;
 use ffmpeg::format::Sample;
 use ffmpeg::util::frame::audio::Audio as FAudio;
 use ffmpeg::{codec, Error};
+use ffmpeg_next as ffmpeg;
 use log::{debug, info, trace, warn};
-use {ffmpeg_next as ffmpeg, taglibxx as taglib};
 
-use crate::error::{MetaError, SeedError};
-use crate::seeders::{Frame, FrameType, Seeder};
-use crate::taggers::Tagger;
+use crate::diagnostics::{capture, Diagnostic};
+use crate::error::SeedError;
+use crate::seeders::{AudioInfo, Corruption, Frame, FrameType, Seeder};
 
 static FFMPEG_STATE: AtomicUsize = AtomicUsize::new(0);
 
@@ -21,7 +22,7 @@ const INITIALIZING: usize = 1;
 const INITIALIZED: usize = 2;
 
 // super safe ffmpeg init
-fn maybe_init() -> Result<(), Error> {
+pub(crate) fn maybe_init() -> Result<(), Error> {
     let old_state = match FFMPEG_STATE.compare_exchange(
         UNINITIALIZED,
         INITIALIZING,
@@ -34,7 +35,8 @@ fn maybe_init() -> Result<(), Error> {
         UNINITIALIZED => {
             debug!("FFMPEG initiating");
             ffmpeg_next::init()?;
-            ffmpeg_next::log::set_level(ffmpeg_next::log::Level::Quiet);
+            // messages are captured per file
+            crate::diagnostics::install();
             debug!("FFMPEG initialized");
             FFMPEG_STATE.store(INITIALIZED, Ordering::SeqCst);
         }
@@ -48,52 +50,138 @@ fn maybe_init() -> Result<(), Error> {
     Ok(())
 }
 
-#[allow(clippy::upper_case_acronyms)]
-enum AvContainer {
-    MP3,
-    FLAC,
-    OGG,
-    MP4,
-    ASF,
-    WAV,
-    WV,
-    AIFF,
-    APE,
-    Unsupported(String),
+/// Max number of planes that can be passed without allocation
+const MAX_PLANES: usize = 64;
+
+/// Buffers for samples that need conversion, reused between frames
+#[derive(Default)]
+struct Converted {
+    i16: Vec<i16>,
+    i32: Vec<i32>,
+}
+
+/// Collects plane slices into fixed buffer on stack
+#[inline]
+fn planes<'a, T: ffmpeg::frame::audio::Sample>(
+    decoded: &'a FAudio,
+    buf: &mut [&'a [T]; MAX_PLANES],
+) -> usize {
+    // checked in `receive`
+    let planes = decoded.planes().min(MAX_PLANES);
+    for (i, p) in buf.iter_mut().enumerate().take(planes) {
+        *p = plane(decoded, i);
+    }
+    planes
+}
+
+/// Passes decoded frame as is
+macro_rules! feed {
+    ($decoded:expr, $variant:path, $d:expr, $f:expr) => {
+        if $decoded.is_planar() {
+            let mut l = [&[][..]; MAX_PLANES];
+            let planes = planes($decoded, &mut l);
+            $f($d, $variant(FrameType::Planar(&l[..planes])))
+        } else {
+            $f($d, $variant(FrameType::Packed(plane($decoded, 0))))
+        }
+    };
+}
+
+/// Converts samples into (packed) buffer, without reallocating it
+#[inline]
+fn convert<S, T>(decoded: &FAudio, buf: &mut Vec<T>, conv: impl Fn(S) -> T)
+where
+    S: ffmpeg::frame::audio::Sample + Copy,
+{
+    buf.clear();
+    if decoded.is_planar() {
+        let mut l = [&[][..]; MAX_PLANES];
+        let planes = planes::<S>(decoded, &mut l);
+        buf.reserve(decoded.samples() * planes);
+        for i in 0..decoded.samples() {
+            buf.extend(l[..planes].iter().map(|p| conv(p[i])));
+        }
+    } else {
+        buf.extend(plane::<S>(decoded, 0).iter().map(|&x| conv(x)));
+    }
 }
 
-impl AvContainer {
-    fn new(s: &str) -> AvContainer {
-        // FFmpeg container short names
-        match s {
-            "mp3" => AvContainer::MP3,
-            "flac" => AvContainer::FLAC,
-            "ogg" => AvContainer::OGG,
-            //"mov" | "mp4" | "m4a" | "3gp" | "3g2" | "mj2" => AvContainer::MP4,
-            "mov,mp4,m4a,3gp,3g2,mj2" => AvContainer::MP4,
-            "asf" => AvContainer::ASF,
-            "wav" => AvContainer::WAV,
-            "wv" => AvContainer::WV,
-            "aiff" => AvContainer::AIFF,
-            "ape" => AvContainer::APE,
-            _ => AvContainer::Unsupported(s.to_owned()),
+/// Passes all frames that decoder has ready to `f`
+fn receive(
+    decoder: &mut ffmpeg::codec::decoder::Audio,
+    decoded: &mut FAudio,
+    converted: &mut Converted,
+    samples: &mut u64,
+    f: &mut dyn FnMut(u64, Frame) -> Result<(), SeedError>,
+) -> Result<(), SeedError> {
+    loop {
+        match decoder.receive_frame(decoded) {
+            Ok(()) => {}
+            // needs more packets or is drained
+            Err(Error::Other {
+                errno: ffmpeg::error::EAGAIN,
+            })
+            | Err(Error::Eof) => break,
+            Err(e) => return Err(SeedError::Damaged(e.to_string())),
+        }
+        trace!("airplanes: {}", decoded.planes());
+        if decoded.is_planar() && decoded.planes() > MAX_PLANES {
+            return Err(SeedError::Unsupported(format!(
+                "{} channels (at most {MAX_PLANES} are supported)",
+                decoded.planes()
+            )));
         }
+        *samples += decoded.samples() as u64;
+        let d = *samples;
+        match decoded.format() {
+            Sample::I16(_) => feed!(decoded, Frame::I16, d, f),
+            Sample::I32(_) => feed!(decoded, Frame::I32, d, f),
+            Sample::F32(_) => feed!(decoded, Frame::F32, d, f),
+            Sample::F64(_) => feed!(decoded, Frame::F64, d, f),
+            Sample::U8(_) => {
+                // unsigned 8 bit to signed 16 bit
+                convert(decoded, &mut converted.i16, |x: u8| {
+                    (i16::from(x) - 128) << 8
+                });
+                f(d, Frame::I16(FrameType::Packed(&converted.i16)))
+            }
+            Sample::I64(_) => {
+                // keep only upper 32 bits (no lib can make use of more)
+                convert(decoded, &mut converted.i32, |x: i64| (x >> 32) as i32);
+                f(d, Frame::I32(FrameType::Packed(&converted.i32)))
+            }
+            Sample::None => panic!("No samples"),
+        }?;
     }
+    Ok(())
 }
 
-/// FFmpeg ang Taglib instance
+/// FFmpeg decoder instance
 pub struct FFtag {
     file: String,
     ictx: ffmpeg::format::context::Input,
     input_idx: usize,
     decoder: ffmpeg::codec::decoder::Audio,
-    container: AvContainer,
     codec_id: ffmpeg::codec::Id,
+    info: AudioInfo,
+    /// decoded so far
+    samples: u64,
+    time_base: ffmpeg::Rational,
+    corruption: Corruption,
+    /// FFmpeg messages about this file
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl FFtag {
-    fn new(path: &Path) -> Result<(Self, u64), Error> {
+    pub fn new(path: &Path) -> Result<(Self, u64), Error> {
         maybe_init()?;
+        let (r, diagnostics) = capture(|| Self::open(path));
+        let (mut ff, len) = r?;
+        ff.diagnostics = diagnostics;
+        Ok((ff, len))
+    }
+
+    fn open(path: &Path) -> Result<(Self, u64), Error> {
         let ictx = ffmpeg::format::input(&path)?;
         let input = ictx
             .streams()
@@ -104,43 +192,70 @@ impl FFtag {
             .decoder()
             .audio()?;
         decoder.set_parameters(input.parameters())?;
-        let len = input.duration() as u64;
+        // stream duration is more precise, but not all containers have it
+        let duration = if input.duration() > 0 {
+            Some(input.duration() as f64 * f64::from(input.time_base()))
+        } else if ictx.duration() > 0 {
+            Some(ictx.duration() as f64 / f64::from(ffmpeg::ffi::AV_TIME_BASE))
+        } else {
+            None
+        };
+        // estimated number of samples
+        let len = duration.map_or(0, |d| (d * f64::from(decoder.rate())).round() as u64);
+        // SAFETY: parameters are valid as long as input is
+        let (bits_per_raw_sample, bitrate) = unsafe {
+            let par = input.parameters().as_ptr();
+            ((*par).bits_per_raw_sample, (*par).bit_rate)
+        };
+        let bitrate = if bitrate > 0 {
+            bitrate
+        } else {
+            ictx.bit_rate()
+        };
+        let info = AudioInfo {
+            rate: decoder.rate(),
+            channels: decoder.channel_layout().channels() as u32,
+            duration,
+            codec: decoder.id().name().to_owned(),
+            container: Some(ictx.format().name().to_owned()),
+            bits_per_raw_sample: u32::try_from(bits_per_raw_sample).ok().filter(|&b| b > 0),
+            bitrate: u64::try_from(bitrate).ok().filter(|&b| b > 0),
+            channel_layout: layout_name(decoder.channel_layout(), decoder.channels()),
+            encoder: input
+                .metadata()
+                .get("encoder")
+                .or_else(|| ictx.metadata().get("encoder"))
+                .map(str::to_owned),
+        };
         Ok((
             Self {
                 file: path.as_os_str().to_string_lossy().to_string(),
-                container: AvContainer::new(ictx.format().name()),
                 codec_id: decoder.id(),
                 ictx,
                 input_idx,
                 decoder,
+                info,
+                samples: 0,
+                time_base: input.time_base(),
+                corruption: Corruption::default(),
+                diagnostics: Vec::new(),
             },
             len,
         ))
     }
-}
 
-impl Seeder for FFtag {
-    fn seed<F>(&mut self, forced: bool, mut f: F) -> Result<(), SeedError>
-    where
-        F: FnMut(u64, Frame) -> Result<(), SeedError>,
-    {
-        let mut sample_type = self.decoder.format();
-
-        let req_resample = match sample_type {
-            // empty
-            Sample::None => panic!("No samples"),
-            // libs cannot handle them so we need to resample
-            Sample::U8(_) | Sample::I64(_) => {
-                sample_type = Sample::I16(Type::Packed);
-                info!("Resampling will be used!");
-                true
-            }
-            // it's fine
-            Sample::I16(_) => false,
-            Sample::I32(_) => false,
-            Sample::F32(_) => false,
-            Sample::F64(_) => false,
-        };
+    /// Decodes whole stream (FFmpeg messages are captured by caller)
+    fn decode(
+        &mut self,
+        forced: bool,
+        f: &mut dyn FnMut(u64, Frame) -> Result<(), SeedError>,
+    ) -> Result<(), SeedError> {
+        let sample_type = self.decoder.format();
+
+        if let Sample::U8(_) | Sample::I64(_) = sample_type {
+            // libs cannot handle them so we need to convert
+            info!("Samples will be converted!");
+        }
 
         /*
             TODO: something that even loudgain does not do
@@ -173,82 +288,91 @@ impl Seeder for FFtag {
 
         debug!("sample: {sample_type:#?}");
 
+        // reused for every frame
+        let mut decoded = FAudio::empty();
+        let mut converted = Converted::default();
+
         for (packet_stream, packet) in self.ictx.packets() {
             if packet_stream.index() == self.input_idx {
-                if let Err(e) = self.decoder.send_packet(&packet) {
-                    if !forced {
-                        return Err(e.into());
+                let result = self
+                    .decoder
+                    .send_packet(&packet)
+                    .map_err(|e| SeedError::Damaged(e.to_string()))
+                    .and_then(|()| {
+                        receive(
+                            &mut self.decoder,
+                            &mut decoded,
+                            &mut converted,
+                            &mut self.samples,
+                            f,
+                        )
+                    });
+                match result {
+                    Err(SeedError::Damaged(e)) if forced => {
+                        warn!("Skipping damaged packet in {}: {e}", self.file);
+                        let tb = f64::from(self.time_base);
+                        let start = match packet.pts().or_else(|| packet.dts()) {
+                            Some(ts) => ts as f64 * tb,
+                            // best guess
+                            None => self.samples as f64 / f64::from(self.decoder.rate()),
+                        };
+                        self.corruption.add(start, packet.duration() as f64 * tb);
                     }
-                    warn!("Error while sending a packet to the decoder {e}");
-                    break;
+                    r => r?,
                 }
-                let mut decoded = FAudio::empty();
-                while self.decoder.receive_frame(&mut decoded).is_ok() {
-                    if req_resample {
-                        let mut resampler = self.decoder.resampler(
-                            Sample::I16(Type::Packed),
-                            self.decoder.channel_layout(),
-                            self.decoder.rate(),
-                        )?;
-                        let mut resampled = FAudio::empty();
-                        resampler.run(&decoded, &mut resampled)?;
-                        decoded = resampled;
-                    }
+            }
+        }
 
-                    let planes = decoded.planes();
-                    trace!("airplanes: {planes}");
-                    debug_assert_eq!(decoded.format(), sample_type);
-                    //let d = dts(&decoded);
-                    // good enought
-                    let d = decoded.pts().unwrap_or_default() as u64;
-                    match sample_type {
-                        Sample::I16(t) => match t {
-                            Type::Packed => f(d, Frame::I16(FrameType::Packed(plane(&decoded, 0)))),
-                            Type::Planar => {
-                                let l: Vec<_> = (0..planes).map(|x| plane(&decoded, x)).collect();
-                                f(d, Frame::I16(FrameType::Planar(&l)))
-                            }
-                        },
-                        Sample::I32(t) => match t {
-                            Type::Packed => f(d, Frame::I32(FrameType::Packed(plane(&decoded, 0)))),
-                            Type::Planar => {
-                                let l: Vec<_> = (0..planes).map(|x| plane(&decoded, x)).collect();
-                                f(d, Frame::I32(FrameType::Planar(&l)))
-                            }
-                        },
-                        Sample::F32(t) => match t {
-                            Type::Packed => f(d, Frame::F32(FrameType::Packed(plane(&decoded, 0)))),
-                            Type::Planar => {
-                                let l: Vec<_> = (0..planes).map(|x| plane(&decoded, x)).collect();
-                                f(d, Frame::F32(FrameType::Planar(&l)))
-                            }
-                        },
-                        Sample::F64(t) => match t {
-                            Type::Packed => f(d, Frame::F64(FrameType::Packed(plane(&decoded, 0)))),
-                            Type::Planar => {
-                                let l: Vec<_> = (0..planes).map(|x| plane(&decoded, x)).collect();
-                                f(d, Frame::F64(FrameType::Planar(&l)))
-                            }
-                        },
-
-                        Sample::None | Sample::U8(_) | Sample::I64(_) => panic!("should not be"),
-                    }?;
-                }
+        // drain frames that decoder still holds
+        self.decoder.send_eof()?;
+        match receive(
+            &mut self.decoder,
+            &mut decoded,
+            &mut converted,
+            &mut self.samples,
+            f,
+        ) {
+            Err(SeedError::Damaged(e)) if forced => {
+                warn!("Damaged data at the end of {}: {e}", self.file);
+                self.corruption
+                    .add(self.samples as f64 / f64::from(self.decoder.rate()), 0.0);
             }
+            r => r?,
         }
 
         Ok(())
     }
+}
+
+impl Seeder for FFtag {
+    fn seed(
+        &mut self,
+        forced: bool,
+        f: &mut dyn FnMut(u64, Frame) -> Result<(), SeedError>,
+    ) -> Result<(), SeedError> {
+        let (r, diagnostics) = capture(|| self.decode(forced, f));
+        self.diagnostics.extend(diagnostics);
+        r
+    }
 
     fn is_opus(&self) -> bool {
         self.codec_id == codec::Id::OPUS
     }
 
-    fn info(&self) -> crate::seeders::AudioInfo {
-        crate::seeders::AudioInfo {
-            rate: self.decoder.rate(),
-            channels: self.decoder.channel_layout().channels() as u32,
-        }
+    fn info(&self) -> &AudioInfo {
+        &self.info
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    fn corruption(&self) -> &Corruption {
+        &self.corruption
+    }
+
+    fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
 }
 
@@ -263,222 +387,28 @@ fn dts(f: &FAudio) -> Option<i64> {
     }
 }*/
 
-impl From<crate::replay_gain::ReplayGain> for taglib::ReplayGain {
-    fn from(x: crate::replay_gain::ReplayGain) -> Self {
-        Self {
-            gain: x.gain,
-            peak: x.peak,
-            loudness_range: x.loudness_range,
-            loudness_reference: x.loudness_reference,
-            loudness: x.loudness,
-        }
-    }
-}
-
-impl Tagger for FFtag {
-    fn do_meta(
-        &self,
-        strip: bool,
-        id3v2version: crate::options::Id3v2version,
-        write: Option<crate::taggers::WriteOptions>,
-        track: Option<crate::replay_gain::ReplayGain>,
-        album: Option<crate::replay_gain::ReplayGain>,
-    ) -> Result<(), MetaError> {
-        use taglib::*;
-        if let Some(wopts) = write {
-            let scan = Scan {
-                file: self.file.clone(),
-                track: track.ok_or(MetaError::NotComputed)?.into(),
-                album: album.unwrap_or_default().into(),
-            };
-            let do_album = album.is_some();
-            let extended = wopts.extended;
-            let unit = wopts.unit;
-            let lowercase = wopts.lowercase;
-            let non_standard_opus = wopts.non_standard_opus;
-            let id3v2version = id3v2version as i32;
-            // write tags
-            match &self.container {
-                AvContainer::MP3 => {
-                    if !tag_write_mp3(
-                        scan,
-                        do_album,
-                        extended,
-                        unit,
-                        lowercase,
-                        strip,
-                        id3v2version,
-                    ) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::FLAC => {
-                    if !tag_write_flac(scan, do_album, extended, unit) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::OGG => match self.codec_id {
-                    codec::Id::OPUS => {
-                        if non_standard_opus {
-                            if !tag_write_ogg_opus_non_standard(scan, do_album, extended, unit) {
-                                return Err(MetaError::Write(self.file.clone()));
-                            }
-                        } else if !tag_write_ogg_opus(scan, do_album, extended, unit) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    codec::Id::VORBIS => {
-                        if !tag_write_ogg_vorbis(scan, do_album, extended, unit) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    codec::Id::FLAC => {
-                        if !tag_write_ogg_flac(scan, do_album, extended, unit) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    codec::Id::SPEEX => {
-                        if !tag_write_ogg_speex(scan, do_album, extended, unit) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    _ => return Err(MetaError::Unsupported(self.codec_id.name().to_owned())),
-                },
-                AvContainer::MP4 => {
-                    if !tag_write_mp4(scan, do_album, extended, unit, lowercase) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::ASF => {
-                    if !tag_write_asf(scan, do_album, extended, unit, lowercase) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::WAV => {
-                    if !tag_write_wav(
-                        scan,
-                        do_album,
-                        extended,
-                        unit,
-                        lowercase,
-                        strip,
-                        id3v2version,
-                    ) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::WV => {
-                    if !tag_write_wavpack(scan, do_album, extended, unit, lowercase, strip) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::AIFF => {
-                    if !tag_write_aiff(
-                        scan,
-                        do_album,
-                        extended,
-                        unit,
-                        lowercase,
-                        strip,
-                        id3v2version,
-                    ) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::APE => {
-                    if !tag_write_ape(scan, do_album, extended, unit, lowercase, strip) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::Unsupported(s) => return Err(MetaError::Unsupported(s.clone())),
-            }
-        } else {
-            // delete tags
-            match &self.container {
-                AvContainer::MP3 => {
-                    if !tag_clear_mp3(self.file.clone(), strip, id3v2version as i32) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::FLAC => {
-                    if !tag_clear_flac(self.file.clone()) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::OGG => match self.codec_id {
-                    codec::Id::OPUS => {
-                        if !tag_clear_ogg_opus(self.file.clone()) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    codec::Id::VORBIS => {
-                        if !tag_clear_ogg_vorbis(self.file.clone()) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    codec::Id::FLAC => {
-                        if !tag_clear_ogg_flac(self.file.clone()) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    codec::Id::SPEEX => {
-                        if !tag_clear_ogg_speex(self.file.clone()) {
-                            return Err(MetaError::Write(self.file.clone()));
-                        }
-                    }
-                    _ => return Err(MetaError::Unsupported(self.codec_id.name().to_owned())),
-                },
-                AvContainer::MP4 => {
-                    if !tag_clear_mp4(self.file.clone()) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::ASF => {
-                    if !tag_clear_asf(self.file.clone()) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::WAV => {
-                    if !tag_clear_wav(self.file.clone(), strip, id3v2version as i32) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::WV => {
-                    if !tag_clear_wavpack(self.file.clone(), strip) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::AIFF => {
-                    if !tag_clear_aiff(self.file.clone(), strip, id3v2version as i32) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::APE => {
-                    if !tag_clear_ape(self.file.clone(), strip) {
-                        return Err(MetaError::Write(self.file.clone()));
-                    }
-                }
-                AvContainer::Unsupported(s) => return Err(MetaError::Unsupported(s.clone())),
-            }
-        }
-        Ok(())
-    }
-}
-
-impl crate::nerics::Nerics for FFtag {
-    fn new(path: &Path) -> Result<(Self, u64), crate::error::NError>
-    where
-        Self: Sized,
-    {
-        Ok(FFtag::new(path)?)
-    }
-}
-
 /*fn map_channel_map(channels_layout: ChannelLayout, n_channels: u16) -> Vec<ebur128::Channel> {
     let channels_layout = channels_layout.bits();
     todo!()
 }*/
 
+/// Name of channel layout (like `stereo` or `5.1(side)`)
+fn layout_name(layout: ffmpeg::ChannelLayout, channels: u16) -> Option<String> {
+    if layout.is_empty() {
+        return None;
+    }
+    let mut buf = [0 as c_char; 64];
+    unsafe {
+        ffmpeg::ffi::av_get_channel_layout_string(
+            buf.as_mut_ptr(),
+            buf.len() as c_int,
+            c_int::from(channels),
+            layout.bits(),
+        );
+        Some(CStr::from_ptr(buf.as_ptr()).to_string_lossy().to_string())
+    }
+}
+
 /// Fix from https://github.com/zmwangx/rust-ffmpeg/pull/104
 #[inline]
 fn plane<T: ffmpeg::frame::audio::Sample>(ss: &FAudio, index: usize) -> &[T] {
@@ -490,7 +420,13 @@ fn plane<T: ffmpeg::frame::audio::Sample>(ss: &FAudio, index: usize) -> &[T] {
     }
 
     if ss.is_planar() {
-        unsafe { std::slice::from_raw_parts((*ss.as_ptr()).data[index] as *const T, ss.samples()) }
+        // extended_data also holds planes beyond AV_NUM_DATA_POINTERS
+        unsafe {
+            std::slice::from_raw_parts(
+                *(*ss.as_ptr()).extended_data.add(index) as *const T,
+                ss.samples(),
+            )
+        }
     } else {
         unsafe {
             std::slice::from_raw_parts(
@@ -0,0 +1,309 @@
+//! Undo journal of tag changes
+//!
+//! Before tags of a file are modified its previous ReplayGain (and DR) tags
+//! (or their absence) are appended to journal, so they can be restored later.
+//!
+//! Format is plain text: header line with start of run (unix time in nanoseconds),
+//! followed by one line per file (path and `KEY=value` or `KEY!` for missing tag,
+//! all separated by tabs). Tabs, newlines, backslashes, `=` and `!` are escaped.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tags that are always recorded in journal (everything mxc can write as RG)
+pub const JOURNAL_TAGS: [&str; 9] = [
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_TRACK_RANGE",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+    "REPLAYGAIN_ALBUM_RANGE",
+    "REPLAYGAIN_REFERENCE_LOUDNESS",
+    "R128_TRACK_GAIN",
+    "R128_ALBUM_GAIN",
+];
+
+const HEADER: &str = "mxc journal 1";
+
+/// Previous tags of one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub file: PathBuf,
+    /// Tag and its value (`None` if tag was not present)
+    pub tags: Vec<(String, Option<String>)>,
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out += "\\\\",
+            '\t' => out += "\\t",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '=' => out += "\\=",
+            '!' => out += "\\!",
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Splits `s` at first `sep` that is not escaped
+fn split_unescaped(s: &str, sep: char) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == sep => return Some((&s[..i], &s[i + c.len_utf8()..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl JournalEntry {
+    fn to_line(&self) -> String {
+        let mut line = escape(&self.file.to_string_lossy());
+        for (key, value) in &self.tags {
+            line.push('\t');
+            line += &escape(key);
+            match value {
+                Some(value) => {
+                    line.push('=');
+                    line += &escape(value);
+                }
+                None => line.push('!'),
+            }
+        }
+        line
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let file = PathBuf::from(unescape(fields.next().filter(|f| !f.is_empty())?));
+        let tags = fields
+            .map(|field| match split_unescaped(field, '=') {
+                Some((key, value)) => Some((unescape(key), Some(unescape(value)))),
+                None => {
+                    let (key, rest) = split_unescaped(field, '!')?;
+                    rest.is_empty().then(|| (unescape(key), None))
+                }
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { file, tags })
+    }
+}
+
+/// Entries of one journal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedJournal {
+    /// Start of run (unix time in nanoseconds), orders journals
+    pub started: u128,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// Journal of one run (can be shared between threads)
+pub struct Journal {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl Journal {
+    /// Creates new journal, fails if file exists (so journal of previous run is not lost)
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    e.kind(),
+                    format!("journal {} already exists", path.display()),
+                ),
+                _ => e,
+            })?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{HEADER}\t{started}")?;
+        writer.flush()?;
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Appends entry and flushes it to disk (before file gets modified)
+    pub fn record(&self, entry: &JournalEntry) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}", entry.to_line())?;
+        writer.flush()?;
+        writer.get_ref().sync_data()
+    }
+}
+
+/// Reads all entries of journal
+pub fn read_journal<P: AsRef<Path>>(path: P) -> io::Result<RecordedJournal> {
+    parse_journal(BufReader::new(File::open(path)?))
+}
+
+fn parse_journal<R: BufRead>(reader: R) -> io::Result<RecordedJournal> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut lines = reader.lines();
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let started = match header.split_once('\t') {
+        Some((HEADER, started)) => started.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid("not a mxc journal".to_owned()))?;
+    let entries = lines
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.is_empty()))
+        .map(|(i, line)| {
+            JournalEntry::from_line(&line?)
+                .ok_or_else(|| invalid(format!("malformed journal line {}", i + 2)))
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(RecordedJournal { started, entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, tags: &[(&str, Option<&str>)]) -> JournalEntry {
+        JournalEntry {
+            file: PathBuf::from(file),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.map(str::to_owned)))
+                .collect(),
+        }
+    }
+
+    fn round_trip(entry: &JournalEntry) {
+        let line = entry.to_line();
+        assert!(!line.contains('\n'), "{line}");
+        assert_eq!(
+            JournalEntry::from_line(&line).as_ref(),
+            Some(entry),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn plain_values() {
+        let e = entry(
+            "music/01 - Intro.flac",
+            &[
+                ("REPLAYGAIN_TRACK_GAIN", Some("-3.21 dB")),
+                ("REPLAYGAIN_TRACK_PEAK", Some("0.988547")),
+                ("R128_TRACK_GAIN", None),
+            ],
+        );
+        assert_eq!(
+            e.to_line(),
+            "music/01 - Intro.flac\tREPLAYGAIN_TRACK_GAIN=-3.21 dB\tREPLAYGAIN_TRACK_PEAK=0.988547\tR128_TRACK_GAIN!"
+        );
+        round_trip(&e);
+    }
+
+    #[test]
+    fn separators_are_escaped() {
+        round_trip(&entry(
+            "a\tb=c!d\\e\nf\rg.flac",
+            &[
+                ("KEY=WITH=EQ", Some("v=a=l")),
+                ("BANG!", Some("!")),
+                ("BANG!", None),
+                ("BACK\\", Some("\\")),
+                ("BACK\\", None),
+                ("TAB\tKEY", Some("multi\nline\r\nvalue\t")),
+            ],
+        ));
+    }
+
+    #[test]
+    fn empty_and_absent_values() {
+        let e = entry("x.mp3", &[("EMPTY", Some("")), ("ABSENT", None)]);
+        assert_eq!(e.to_line(), "x.mp3\tEMPTY=\tABSENT!");
+        round_trip(&e);
+        // file without any tags
+        round_trip(&entry("x.mp3", &[]));
+    }
+
+    #[test]
+    fn malformed_lines() {
+        assert_eq!(JournalEntry::from_line(""), None);
+        assert_eq!(JournalEntry::from_line("\tKEY!"), None);
+        assert_eq!(JournalEntry::from_line("x.mp3\tKEY"), None);
+        assert_eq!(JournalEntry::from_line("x.mp3\tKEY!x"), None);
+        assert_eq!(JournalEntry::from_line("x.mp3\tKEY\\!"), None);
+    }
+
+    #[test]
+    fn journal_has_start_of_run() {
+        let e = entry("x.mp3", &[("DYNAMIC RANGE", Some("12"))]);
+        let text = format!("{HEADER}\t42\n{}\n\n", e.to_line());
+        let journal = parse_journal(text.as_bytes()).unwrap();
+        assert_eq!(journal.started, 42);
+        assert_eq!(journal.entries, [e]);
+        assert!(parse_journal(format!("{HEADER}\n").as_bytes()).is_err());
+        assert!(parse_journal("mxc journal 2\t42\n".as_bytes()).is_err());
+        assert!(parse_journal(format!("{HEADER}\t42\nx.mp3\tKEY\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn create_record_read() {
+        let path = std::env::temp_dir().join(format!("mxc-journal-{}", std::process::id()));
+        let e = entry("x.mp3", &[("REPLAYGAIN_TRACK_GAIN", None)]);
+        let journal = Journal::create(&path).unwrap();
+        journal.record(&e).unwrap();
+        journal.record(&e).unwrap();
+        drop(journal);
+        let read = read_journal(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(read.started > 0);
+        assert_eq!(read.entries, [e.clone(), e]);
+    }
+
+    #[test]
+    fn existing_journal_is_kept() {
+        let path = std::env::temp_dir().join(format!("mxc-journal-kept-{}", std::process::id()));
+        let e = entry("x.mp3", &[("REPLAYGAIN_TRACK_GAIN", Some("-1.00 dB"))]);
+        Journal::create(&path).unwrap().record(&e).unwrap();
+        let second = Journal::create(&path);
+        let read = read_journal(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            second.err().map(|e| e.kind()),
+            Some(io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(read.entries, [e]);
+    }
+}
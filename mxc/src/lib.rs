@@ -1,8 +1,15 @@
 /* Exporting */
 mod audiofile;
 pub use audiofile::*;
+mod diagnostics;
+pub use diagnostics::Diagnostic;
 mod error;
 pub use error::*;
+pub mod analyzers;
+pub mod compliance;
+pub mod dr_log;
+pub mod journal;
+pub use seeders::{AudioInfo, Corruption, Frame, FrameType};
 // here are generic options, that are to be used as lib
 pub mod options;
 pub mod replay_gain;
@@ -11,11 +18,16 @@ pub mod walker;
 pub mod version {
     pub use ffmpeg_next::format::version as libavformat_version;
     pub use ffmpeg_next::software::resampling::version as libswr_version;
+    #[cfg(feature = "taglib")]
     pub use taglibxx::{tag_version_major, tag_version_minor, tag_version_patch};
 }
 
+#[cfg(not(any(feature = "taglib", feature = "lofty")))]
+compile_error!("At least one of taglib or lofty features must be enabled");
+
 // internal modules that are not exported
 mod fftag;
-mod nerics;
+mod pipeline;
+mod safe_write;
 mod seeders;
 mod taggers;
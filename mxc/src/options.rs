@@ -18,3 +18,111 @@ impl std::str::FromStr for Id3v2version {
         }
     }
 }
+
+/// Backend used for decoding
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Decoder {
+    /// FFmpeg (supports everything)
+    #[default]
+    FFmpeg,
+    /// Pure Rust decoder (falls back to FFmpeg for unsupported formats)
+    Symphonia,
+}
+
+impl std::str::FromStr for Decoder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ffmpeg" | "ff" => Ok(Self::FFmpeg),
+            "symphonia" => Ok(Self::Symphonia),
+            _ => Err(format!(
+                "invalid decoder `{s}`; only ffmpeg and symphonia are supported"
+            )),
+        }
+    }
+}
+
+/// Backend used for writing tags
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Tagger {
+    /// TagLib (C++)
+    TagLib,
+    /// Pure Rust tagger
+    Lofty,
+}
+
+impl Default for Tagger {
+    fn default() -> Self {
+        if cfg!(feature = "taglib") {
+            Self::TagLib
+        } else {
+            Self::Lofty
+        }
+    }
+}
+
+impl std::str::FromStr for Tagger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "taglib" => Ok(Self::TagLib),
+            "lofty" => Ok(Self::Lofty),
+            _ => Err(format!(
+                "invalid tagger `{s}`; only taglib and lofty are supported"
+            )),
+        }
+    }
+}
+
+/// Frequency axis of spectrogram
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum FrequencyScale {
+    #[default]
+    Linear,
+    /// Logarithmic (from 20 Hz), more space for bass
+    Log,
+}
+
+impl std::str::FromStr for FrequencyScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "linear" | "lin" => Ok(Self::Linear),
+            "log" | "logarithmic" => Ok(Self::Log),
+            _ => Err(format!(
+                "invalid frequency scale `{s}`; only linear and log are supported"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoder_from_str() {
+        assert_eq!(Decoder::default(), Decoder::FFmpeg);
+        assert_eq!("FFmpeg".parse(), Ok(Decoder::FFmpeg));
+        assert_eq!("ff".parse(), Ok(Decoder::FFmpeg));
+        assert_eq!("symphonia".parse(), Ok(Decoder::Symphonia));
+        assert!("gstreamer".parse::<Decoder>().is_err());
+    }
+
+    #[test]
+    fn tagger_from_str() {
+        assert_eq!("TagLib".parse(), Ok(Tagger::TagLib));
+        assert_eq!("lofty".parse(), Ok(Tagger::Lofty));
+        assert!("mutagen".parse::<Tagger>().is_err());
+    }
+
+    #[test]
+    fn frequency_scale_from_str() {
+        assert_eq!("lin".parse(), Ok(FrequencyScale::Linear));
+        assert_eq!("Log".parse(), Ok(FrequencyScale::Log));
+        assert!("mel".parse::<FrequencyScale>().is_err());
+    }
+}
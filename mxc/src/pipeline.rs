@@ -0,0 +1,137 @@
+use std::sync::mpsc::sync_channel;
+use std::sync::Arc;
+
+use crate::analyzers::Analyzer;
+use crate::error::SeedError;
+use crate::seeders::{Frame, FrameType, Seeder};
+
+/// How many frames can wait for each analyzer
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Owned (packed) copy of [Frame], so it can be sent to analyzer threads
+enum Samples {
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+/// Copies (and interleaves if planar) frame into buffer without reallocating
+fn copy_into<T: Copy>(buf: &mut Vec<T>, frame: &FrameType<T>) {
+    buf.clear();
+    match frame {
+        FrameType::Packed(x) => buf.extend_from_slice(x),
+        FrameType::Planar(planes) => {
+            let len = planes.first().map_or(0, |p| p.len());
+            buf.reserve(len * planes.len());
+            for i in 0..len {
+                buf.extend(planes.iter().map(|p| p[i]));
+            }
+        }
+    }
+}
+
+impl Samples {
+    fn fill(&mut self, frame: &Frame) {
+        match (&mut *self, frame) {
+            (Samples::I16(buf), Frame::I16(x)) => copy_into(buf, x),
+            (Samples::I32(buf), Frame::I32(x)) => copy_into(buf, x),
+            (Samples::F32(buf), Frame::F32(x)) => copy_into(buf, x),
+            (Samples::F64(buf), Frame::F64(x)) => copy_into(buf, x),
+            // sample type changed (should not happen), so we need new buffer
+            _ => {
+                *self = match frame {
+                    Frame::I16(_) => Samples::I16(Vec::new()),
+                    Frame::I32(_) => Samples::I32(Vec::new()),
+                    Frame::F32(_) => Samples::F32(Vec::new()),
+                    Frame::F64(_) => Samples::F64(Vec::new()),
+                };
+                self.fill(frame)
+            }
+        }
+    }
+
+    fn frame(&self) -> Frame {
+        match self {
+            Samples::I16(x) => Frame::I16(FrameType::Packed(x)),
+            Samples::I32(x) => Frame::I32(FrameType::Packed(x)),
+            Samples::F32(x) => Frame::F32(FrameType::Packed(x)),
+            Samples::F64(x) => Frame::F64(FrameType::Packed(x)),
+        }
+    }
+}
+
+/// Pool of frame buffers.
+///
+/// Buffer is reused once all analyzers are done with it (nobody else holds the [Arc]).
+#[derive(Default)]
+struct Pool {
+    buffers: Vec<Arc<Samples>>,
+}
+
+impl Pool {
+    fn get(&mut self, frame: &Frame) -> Arc<Samples> {
+        let i = match self
+            .buffers
+            .iter_mut()
+            .position(|b| Arc::get_mut(b).is_some())
+        {
+            Some(i) => i,
+            None => {
+                self.buffers.push(Arc::new(Samples::I16(Vec::new())));
+                self.buffers.len() - 1
+            }
+        };
+        let buf = &mut self.buffers[i];
+        Arc::get_mut(buf).unwrap().fill(frame);
+        Arc::clone(buf)
+    }
+}
+
+/// Decode on current thread and feed every analyzer on its own thread.
+///
+/// Analyzers are only fed here, finalizing is left to caller.
+pub(crate) fn seed_pipelined(
+    seeder: &mut dyn Seeder,
+    forced: bool,
+    analyzers: Vec<&mut dyn Analyzer>,
+    progress: &mut dyn FnMut(u64),
+) -> Result<(), SeedError> {
+    std::thread::scope(|s| {
+        let mut senders = Vec::with_capacity(analyzers.len());
+        let mut handles = Vec::with_capacity(analyzers.len());
+        for analyzer in analyzers {
+            let (tx, rx) = sync_channel::<Arc<Samples>>(CHANNEL_CAPACITY);
+            senders.push(tx);
+            handles.push(s.spawn(move || -> Result<(), SeedError> {
+                for samples in rx {
+                    analyzer.feed(&samples.frame())?;
+                }
+                Ok(())
+            }));
+        }
+
+        let mut pool = Pool::default();
+        let decoded = seeder.seed(forced, &mut |d, frame| {
+            progress(d);
+            let samples = pool.get(&frame);
+            for tx in &senders {
+                if tx.send(Arc::clone(&samples)).is_err() {
+                    // analyzer thread stopped because of error, that is returned on join
+                    return Err(SeedError::Internal("analyzer stopped".into()));
+                }
+            }
+            Ok(())
+        });
+        // close channels so analyzer threads finish
+        drop(senders);
+
+        // errors from analyzers are the real cause, so they take precedence
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| SeedError::Internal("analyzer thread panicked".into()))??;
+        }
+        decoded
+    })
+}
@@ -1,7 +1,69 @@
+use std::str::FromStr;
+
 use ebur128::{EbuR128, Error};
 use log::debug;
 
 use crate::audiofile::AudioFile;
+use crate::compliance;
+
+/// Reference loudness of ReplayGain 2 in LUFS
+pub const RG_REFERENCE: f64 = -18.0;
+
+/// Reference loudness of Opus R128 gains in LUFS (RFC 7845)
+pub const R128_REFERENCE: f64 = -23.0;
+
+/// Loudness (LUFS) that gains normalize to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target(pub f64);
+
+impl Default for Target {
+    fn default() -> Self {
+        Self(RG_REFERENCE)
+    }
+}
+
+impl Target {
+    /// Target of loudgain style pregain (dB/LU on top of -18 LUFS)
+    pub fn from_pregain(pregain: f64) -> Self {
+        Self(RG_REFERENCE + pregain)
+    }
+
+    pub fn pregain(&self) -> f64 {
+        self.0 - RG_REFERENCE
+    }
+
+    /// Named preset (`replaygain` or any of [compliance::PRESETS])
+    pub fn preset(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("replaygain") || name.eq_ignore_ascii_case("rg") {
+            Some(Self::default())
+        } else {
+            compliance::preset(name).map(|p| Self(p.target))
+        }
+    }
+
+    /// Opus R128 gains are relative to -23 LUFS,
+    /// so target keeps its offset from ReplayGain reference
+    pub fn opus(&self) -> Self {
+        Self(R128_REFERENCE + self.pregain())
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(lufs) if lufs.is_finite() => Ok(Self(lufs)),
+            _ => Self::preset(s).ok_or_else(|| format!("Invalid target: {s} (LUFS or preset)")),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} LUFS", self.0)
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct ReplayGain {
@@ -24,7 +86,7 @@ impl std::fmt::Display for ReplayGain {
             f,
             "Peak:     {:8.6} ({:.2} dBTP)",
             self.peak,
-            lufs_to_dbtp(self.peak)
+            linear_to_db(self.peak)
         )?;
         write!(f, "Gain:     {:8.2} dB", self.gain)
     }
@@ -37,22 +99,22 @@ impl ReplayGain {
         println!(
             "Peak:     {:8.6} ({:.2} dBTP)",
             self.peak,
-            lufs_to_dbtp(self.peak)
+            linear_to_db(self.peak)
         );
         println!("Gain:     {:8.2} {unit}", self.gain);
     }
 
     /// Detect clip and prevent it if requested
     pub fn clipper(&mut self, max_true_peak_level: f64, prevent: bool) -> Clipper {
-        let peak_limit = dbtp_to_lufs(max_true_peak_level);
+        let peak_limit = db_to_linear(max_true_peak_level);
         // new peak after gain
-        let new_peak = dbtp_to_lufs(self.gain) * self.peak;
+        let new_peak = db_to_linear(self.gain) * self.peak;
 
         if new_peak > peak_limit {
             if prevent {
                 let new_new_peak = new_peak.min(peak_limit);
                 debug!("Clipping prevented");
-                self.gain -= lufs_to_dbtp(new_peak / new_new_peak);
+                self.gain -= linear_to_db(new_peak / new_new_peak);
                 return (*self, (false, true, new_new_peak));
             }
             (*self, (true, false, new_peak))
@@ -62,11 +124,58 @@ impl ReplayGain {
     }
 }
 
+/// Tags that [StoredRg] is read from
+pub const RG_TAGS: [&str; 6] = [
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+    "R128_TRACK_GAIN",
+    "R128_ALBUM_GAIN",
+];
+
+/// ReplayGain values that are already stored in file tags
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct StoredRg {
+    /// in dB (Opus R128 gains are converted from Q7.8)
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl StoredRg {
+    /// Parses values of [RG_TAGS] (in that order)
+    pub fn from_tags(values: &[Option<String>]) -> Self {
+        // "-3.21 dB" -> -3.21
+        let num = |i: usize| -> Option<f64> {
+            values
+                .get(i)?
+                .as_ref()?
+                .split_whitespace()
+                .next()?
+                .parse()
+                .ok()
+        };
+        let q78 = |i: usize| num(i).map(|x| x / 256.0);
+        Self {
+            track_gain: num(0).or_else(|| q78(4)),
+            track_peak: num(1),
+            album_gain: num(2).or_else(|| q78(5)),
+            album_peak: num(3),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// (will_clip, clip_prevented, new_peak)
 pub type Clipper = (ReplayGain, (bool, bool, f64));
 
-/// Calculates ReplayGain(2) with -18.00 LUFS
-pub fn track_rg(e: &EbuR128, pregain: f64) -> Result<ReplayGain, Error> {
+/// Calculates ReplayGain(2) that normalizes to `target` (-18.00 LUFS by default)
+pub fn track_rg(e: &EbuR128, target: Target) -> Result<ReplayGain, Error> {
     let global = e.loudness_global()?;
     let range = e.loudness_range()?;
     let peak = (0..e.channels())
@@ -81,15 +190,16 @@ pub fn track_rg(e: &EbuR128, pregain: f64) -> Result<ReplayGain, Error> {
     //let peak = peak.into_iter().reduce(f64::max).unwrap();
 
     Ok(ReplayGain {
-        gain: lufs_to_rg(global) + pregain,
+        gain: target.0 - global,
         peak,
         loudness: global,
         loudness_range: range,
-        loudness_reference: lufs_to_rg(-pregain),
+        loudness_reference: target.0,
     })
 }
 
-pub fn album_rg(files: &[AudioFile], pregain: f64) -> Result<ReplayGain, Error> {
+/// Calculates album ReplayGain(2) that normalizes to `target`
+pub fn album_rg(files: &[AudioFile], target: Target) -> Result<ReplayGain, Error> {
     let global = EbuR128::loudness_global_multiple(files.iter().map(|x| x.ebur.as_ref().unwrap()))?;
     let range = EbuR128::loudness_range_multiple(files.iter().map(|x| x.ebur.as_ref().unwrap()))?;
 
@@ -100,28 +210,28 @@ pub fn album_rg(files: &[AudioFile], pregain: f64) -> Result<ReplayGain, Error>
         .unwrap();
 
     Ok(ReplayGain {
-        gain: lufs_to_rg(global) + pregain,
+        gain: target.0 - global,
         peak,
         loudness: global,
         loudness_range: range,
-        loudness_reference: lufs_to_rg(-pregain),
+        loudness_reference: target.0,
     })
 }
 
 #[inline]
 pub fn lufs_to_rg(l: f64) -> f64 {
-    -18.0 - l
+    RG_REFERENCE - l
 }
 
 #[inline]
-/// The equation to convert to dBTP is: 20 * log10(n)
-pub fn lufs_to_dbtp(n: f64) -> f64 {
+/// Linear amplitude (peak) to dB (dBTP for true peak): 20 * log10(n)
+pub fn linear_to_db(n: f64) -> f64 {
     20.0 * (n).log10()
 }
 
 #[inline]
-/// The equation to convert to LUFS is: 10 ** (n / 20.0)
-pub fn dbtp_to_lufs(n: f64) -> f64 {
+/// dB (gain or dBTP) to linear amplitude: 10 ** (n / 20.0)
+pub fn db_to_linear(n: f64) -> f64 {
     10.0_f64.powf(n / 20.0)
 }
 
@@ -184,3 +294,81 @@ pub trait MapOkTrait {
 
 impl<I, T, E> MapOkTrait for I where I: Sized + Iterator<Item = Result<T, E>> {}
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_from_str() {
+        assert_eq!("-16".parse(), Ok(Target(-16.0)));
+        assert_eq!("-14.5".parse(), Ok(Target(-14.5)));
+        assert_eq!("replaygain".parse(), Ok(Target(RG_REFERENCE)));
+        assert_eq!("RG".parse(), Ok(Target(RG_REFERENCE)));
+        assert_eq!("EBU".parse(), Ok(Target(-23.0)));
+        assert_eq!("spotify".parse(), Ok(Target(-14.0)));
+        assert!("inf".parse::<Target>().is_err());
+        assert!("NaN".parse::<Target>().is_err());
+        assert!("loud".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn target_pregain() {
+        assert_eq!(Target::default().pregain(), 0.0);
+        assert_eq!(Target::from_pregain(4.0), Target(-14.0));
+        assert_eq!(Target(-14.0).pregain(), 4.0);
+    }
+
+    #[test]
+    fn opus_target_keeps_offset() {
+        assert_eq!(Target::default().opus(), Target(R128_REFERENCE));
+        assert_eq!(Target(-14.0).opus(), Target(-19.0));
+        assert_eq!(Target(-23.0).opus(), Target(-28.0));
+    }
+
+    fn tags(values: [Option<&str>; 6]) -> Vec<Option<String>> {
+        values.iter().map(|v| v.map(str::to_owned)).collect()
+    }
+
+    #[test]
+    fn stored_rg_from_tags() {
+        let rg = StoredRg::from_tags(&tags([
+            Some("-3.21 dB"),
+            Some("0.988547"),
+            Some("-4.50"),
+            Some("1.000000"),
+            None,
+            None,
+        ]));
+        assert_eq!(rg.track_gain, Some(-3.21));
+        assert_eq!(rg.track_peak, Some(0.988547));
+        assert_eq!(rg.album_gain, Some(-4.5));
+        assert_eq!(rg.album_peak, Some(1.0));
+    }
+
+    #[test]
+    fn stored_rg_from_opus_tags() {
+        // Q7.8
+        let rg = StoredRg::from_tags(&tags([None, None, None, None, Some("-1280"), Some("384")]));
+        assert_eq!(rg.track_gain, Some(-5.0));
+        assert_eq!(rg.album_gain, Some(1.5));
+        assert_eq!(rg.track_peak, None);
+        // REPLAYGAIN_* wins
+        let rg = StoredRg::from_tags(&tags([Some("2 dB"), None, None, None, Some("-1280"), None]));
+        assert_eq!(rg.track_gain, Some(2.0));
+    }
+
+    #[test]
+    fn stored_rg_ignores_garbage() {
+        let rg = StoredRg::from_tags(&tags([
+            Some(""),
+            Some("loud"),
+            Some(" dB"),
+            None,
+            None,
+            None,
+        ]));
+        assert!(rg.is_empty());
+        assert!(StoredRg::from_tags(&[]).is_empty());
+    }
+}
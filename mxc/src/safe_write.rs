@@ -0,0 +1,396 @@
+//! Crash-safe tagging
+//!
+//! Tags are written into temporary copy (in the same directory, so rename is atomic),
+//! that is verified (decoded and tags read back) and then renamed over the original file.
+//! Crash or full disk can only leave stray temporary file behind.
+//!
+//! Symlinks are resolved (their target is replaced) and files with more hard links
+//! are refused, as replacing them would split the links.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::audiofile::{open_seeder, open_tagger};
+use crate::error::{MetaError, NError, SeedError};
+use crate::options;
+use crate::replay_gain::{ReplayGain, StoredRg, RG_TAGS};
+use crate::taggers::Tagger;
+
+/// Frames decoded from tagged copy (enough to get past tags into audio)
+const VERIFY_FRAMES: u64 = 16;
+
+/// Gains are written with two decimals (Opus as Q7.8)
+const GAIN_TOLERANCE: f64 = 0.01;
+
+/// Peaks are written with six decimals
+const PEAK_TOLERANCE: f64 = 1e-5;
+
+/// Tags that must read back from tagged copy
+pub(crate) enum Expected<'a> {
+    /// Exact values (`None` is removed tag), empty only checks that tags are readable
+    Tags(&'a [(&'a str, Option<String>)]),
+    /// Written ReplayGain (values are rounded when written, so they are compared with tolerance)
+    ReplayGain {
+        track: ReplayGain,
+        album: Option<ReplayGain>,
+    },
+}
+
+/// `dir/.name.mxc-RANDOM.ext` (extension is kept, as some taggers detect format from it)
+fn temp_path(file: &Path) -> PathBuf {
+    // every RandomState has random keys
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+    let mut tmp = format!(".{name}.mxc-{:016x}", hasher.finish());
+    if let Some(ext) = file.extension() {
+        tmp = tmp + "." + &ext.to_string_lossy();
+    }
+    file.with_file_name(tmp)
+}
+
+/// Creates temporary file that did not exist before (never touches someone else's file)
+fn create_temp(file: &Path) -> io::Result<(PathBuf, File)> {
+    let mut tries = 0;
+    loop {
+        let tmp = temp_path(file);
+        match File::options().write(true).create_new(true).open(&tmp) {
+            Ok(copy) => return Ok((tmp, copy)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && tries < 16 => tries += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn verification_failed<E: std::fmt::Display>(file: &Path, e: E) -> MetaError {
+    MetaError::Write(format!(
+        "{} (verification of tagged copy failed: {e})",
+        file.display()
+    ))
+}
+
+/// Decodes first frames with selected decoder
+fn verify_audio(path: &Path, decoder: options::Decoder) -> Result<(), String> {
+    let (mut seeder, _) = open_seeder(path, decoder).map_err(|e| e.to_string())?;
+    let mut frames = 0;
+    let result = seeder.seed(false, &mut |_, _| {
+        frames += 1;
+        if frames == VERIFY_FRAMES {
+            // stops decoding
+            return Err(SeedError::Internal("enough frames".into()));
+        }
+        Ok(())
+    });
+    match result {
+        _ if frames == VERIFY_FRAMES => Ok(()),
+        Err(e) => Err(e.to_string()),
+        Ok(()) if frames > 0 => Ok(()),
+        Ok(()) => Err("no audio could be decoded".to_owned()),
+    }
+}
+
+/// Reads back tags that were just written
+fn verify_tags(tagger: &dyn Tagger, expected: &Expected) -> Result<(), String> {
+    let close = |name: &str, got: Option<f64>, want: f64, tolerance: f64| match got {
+        Some(got) if (got - want).abs() <= tolerance => Ok(()),
+        got => Err(format!("{name} is {got:?} instead of {want}")),
+    };
+    match expected {
+        Expected::Tags(tags) => {
+            let keys: Vec<&str> = tags.iter().map(|(key, _)| *key).collect();
+            let values = tagger.read_custom(&keys).map_err(|e| e.to_string())?;
+            for ((key, want), got) in tags.iter().zip(values) {
+                if *want != got {
+                    return Err(format!("{key} is {got:?} instead of {want:?}"));
+                }
+            }
+        }
+        Expected::ReplayGain { track, album } => {
+            let values = tagger.read_custom(&RG_TAGS).map_err(|e| e.to_string())?;
+            let stored = StoredRg::from_tags(&values);
+            close("track gain", stored.track_gain, track.gain, GAIN_TOLERANCE)?;
+            // Opus has no peaks
+            if stored.track_peak.is_some() {
+                close("track peak", stored.track_peak, track.peak, PEAK_TOLERANCE)?;
+            }
+            if let Some(album) = album {
+                close("album gain", stored.album_gain, album.gain, GAIN_TOLERANCE)?;
+                if stored.album_peak.is_some() {
+                    close("album peak", stored.album_peak, album.peak, PEAK_TOLERANCE)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `op` with tagger of temporary copy of `file` and replaces `file` with it
+/// (after copy is verified with `decoder` and `expected` tags)
+pub(crate) fn safe_write<F>(
+    file: &Path,
+    decoder: options::Decoder,
+    tagger: options::Tagger,
+    expected: &Expected,
+    op: F,
+) -> Result<(), MetaError>
+where
+    F: FnOnce(&dyn Tagger) -> Result<(), MetaError>,
+{
+    safe_write_with(
+        file,
+        decoder,
+        &|path| open_tagger(path, tagger),
+        expected,
+        op,
+    )
+}
+
+/// Opens tagger of file (so tests can use their own)
+type OpenTagger<'a> = &'a dyn Fn(&Path) -> Result<Box<dyn Tagger + Send>, NError>;
+
+fn safe_write_with<F>(
+    file: &Path,
+    decoder: options::Decoder,
+    open: OpenTagger,
+    expected: &Expected,
+    op: F,
+) -> Result<(), MetaError>
+where
+    F: FnOnce(&dyn Tagger) -> Result<(), MetaError>,
+{
+    // replace target of symlink instead of link itself
+    let file = fs::canonicalize(file)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let links = fs::metadata(&file)?.nlink();
+        if links > 1 {
+            return Err(MetaError::Unsupported(format!(
+                "{} has {links} hard links (safe write would split them)",
+                file.display()
+            )));
+        }
+    }
+    let (tmp, copy) = create_temp(&file)?;
+    let result = write_and_replace(&file, &tmp, copy, decoder, open, expected, op);
+    if result.is_err() {
+        // original is untouched
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn write_and_replace<F>(
+    file: &Path,
+    tmp: &Path,
+    mut copy: File,
+    decoder: options::Decoder,
+    open: OpenTagger,
+    expected: &Expected,
+    op: F,
+) -> Result<(), MetaError>
+where
+    F: FnOnce(&dyn Tagger) -> Result<(), MetaError>,
+{
+    let metadata = fs::metadata(file)?;
+    io::copy(&mut File::open(file)?, &mut copy)?;
+    drop(copy);
+
+    let tmp_tagger = open(tmp).map_err(|e| MetaError::Internal(Box::new(e)))?;
+    op(tmp_tagger.as_ref())?;
+    drop(tmp_tagger);
+
+    // copy must still be playable and have the new tags
+    verify_audio(tmp, decoder).map_err(|e| verification_failed(file, e))?;
+    let tmp_tagger = open(tmp).map_err(|e| verification_failed(file, e))?;
+    verify_tags(tmp_tagger.as_ref(), expected).map_err(|e| verification_failed(file, e))?;
+    drop(tmp_tagger);
+
+    let copy = File::options().write(true).open(tmp)?;
+    copy.set_modified(metadata.modified()?)?;
+    copy.sync_all()?;
+    drop(copy);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let owner = (metadata.uid(), metadata.gid());
+        let tmp_metadata = fs::metadata(tmp)?;
+        // only root can give file away, so chown is skipped when copy already has same owner
+        if owner != (tmp_metadata.uid(), tmp_metadata.gid()) {
+            std::os::unix::fs::chown(tmp, Some(owner.0), Some(owner.1)).map_err(|e| {
+                match e.kind() {
+                    io::ErrorKind::PermissionDenied => MetaError::Unsupported(format!(
+                        "{} is owned by another user or group (safe write cannot preserve ownership)",
+                        file.display()
+                    )),
+                    _ => e.into(),
+                }
+            })?;
+        }
+    }
+    // chown could clear setuid/setgid bits
+    fs::set_permissions(tmp, metadata.permissions())?;
+
+    fs::rename(tmp, file)?;
+    // persist rename
+    #[cfg(unix)]
+    {
+        if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Id3v2version;
+    use crate::taggers::WriteOptions;
+
+    /// Empty directory for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mxc-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Tagger that only reads fixed tags
+    struct Fixed(Vec<(&'static str, &'static str)>);
+
+    impl Tagger for Fixed {
+        fn do_meta(
+            &self,
+            _: bool,
+            _: Id3v2version,
+            _: Option<WriteOptions>,
+            _: Option<ReplayGain>,
+            _: Option<ReplayGain>,
+        ) -> Result<(), MetaError> {
+            Err(MetaError::Unsupported("read-only test tagger".to_owned()))
+        }
+
+        fn do_custom(
+            &self,
+            _: Id3v2version,
+            _: &[(&str, Option<String>)],
+        ) -> Result<(), MetaError> {
+            Err(MetaError::Unsupported("read-only test tagger".to_owned()))
+        }
+
+        fn read_custom(&self, keys: &[&str]) -> Result<Vec<Option<String>>, MetaError> {
+            Ok(keys
+                .iter()
+                .map(|key| {
+                    self.0
+                        .iter()
+                        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                        .map(|(_, v)| v.to_string())
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn temp_paths_are_unique() {
+        let file = Path::new("music/01 - Intro.flac");
+        let (a, b) = (temp_path(file), temp_path(file));
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), file.parent());
+        assert_eq!(a.extension().unwrap(), "flac");
+        assert!(a
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(".01 - Intro.mxc-"));
+    }
+
+    #[test]
+    fn failed_op_leaves_original() {
+        let dir = test_dir("safe-write-failed");
+        let file = dir.join("song.flac");
+        let content: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        fs::write(&file, &content).unwrap();
+        let open =
+            |_: &Path| -> Result<Box<dyn Tagger + Send>, NError> { Ok(Box::new(Fixed(vec![]))) };
+        let result = safe_write_with(
+            &file,
+            options::Decoder::default(),
+            &open,
+            &Expected::Tags(&[]),
+            |tagger| tagger.do_custom(Id3v2version::V4, &[("DYNAMIC RANGE", None)]),
+        );
+        // error comes from op
+        assert!(
+            matches!(&result, Err(MetaError::Unsupported(e)) if e == "read-only test tagger"),
+            "{result:?}"
+        );
+        assert_eq!(fs::read(&file).unwrap(), content);
+        // no temporary file left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_are_refused() {
+        let dir = test_dir("safe-write-links");
+        let file = dir.join("song.flac");
+        fs::write(&file, b"fLaC").unwrap();
+        fs::hard_link(&file, dir.join("link.flac")).unwrap();
+        let result = safe_write(
+            &file,
+            options::Decoder::default(),
+            options::Tagger::default(),
+            &Expected::Tags(&[]),
+            |_| panic!("hard linked file must not be tagged"),
+        );
+        assert!(matches!(result, Err(MetaError::Unsupported(_))));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn written_tags_are_verified() {
+        let tagger = Fixed(vec![("DYNAMIC RANGE", "12")]);
+        let ok = [
+            ("Dynamic Range", Some("12".to_owned())),
+            ("ALBUM DYNAMIC RANGE", None),
+        ];
+        assert_eq!(verify_tags(&tagger, &Expected::Tags(&ok)), Ok(()));
+        let wrong = [("DYNAMIC RANGE", Some("13".to_owned()))];
+        assert!(verify_tags(&tagger, &Expected::Tags(&wrong)).is_err());
+        let removed = [("DYNAMIC RANGE", None)];
+        assert!(verify_tags(&tagger, &Expected::Tags(&removed)).is_err());
+    }
+
+    #[test]
+    fn written_replay_gain_is_verified() {
+        let rg = |gain, peak| ReplayGain {
+            gain,
+            peak,
+            ..Default::default()
+        };
+        let tagger = Fixed(vec![
+            ("REPLAYGAIN_TRACK_GAIN", "-3.21 dB"),
+            ("REPLAYGAIN_TRACK_PEAK", "0.988547"),
+        ]);
+        let expected = |track, album| Expected::ReplayGain { track, album };
+        assert!(verify_tags(&tagger, &expected(rg(-3.2149, 0.98854712), None)).is_ok());
+        assert!(verify_tags(&tagger, &expected(rg(-3.5, 0.988547), None)).is_err());
+        assert!(verify_tags(&tagger, &expected(rg(-3.21, 0.9), None)).is_err());
+        // album was not written
+        let album = Some(rg(-4.0, 1.0));
+        assert!(verify_tags(&tagger, &expected(rg(-3.21, 0.988547), album)).is_err());
+        // Opus (Q7.8 and no peaks)
+        let opus = Fixed(vec![
+            ("R128_TRACK_GAIN", "-822"),
+            ("R128_ALBUM_GAIN", "-1024"),
+        ]);
+        assert!(verify_tags(&opus, &expected(rg(-3.21, 0.98), Some(rg(-4.0, 1.0)))).is_ok());
+    }
+}
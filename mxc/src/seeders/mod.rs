@@ -1,32 +1,191 @@
+use std::fmt::Display;
+
+use crate::diagnostics::Diagnostic;
 use crate::error::SeedError;
 
+#[cfg(feature = "symphonia")]
+mod symphonia;
+#[cfg(feature = "symphonia")]
+pub use self::symphonia::SymphoniaSeeder;
+
 pub trait Seeder {
     /// Seed ebur
-    fn seed<F>(&mut self, forced: bool, f: F) -> Result<(), SeedError>
-    where
-        F: FnMut(u64, Frame) -> Result<(), SeedError>;
+    ///
+    /// `f` receives number of samples (per channel) decoded so far and the frame.
+    /// Decoder is drained at the end, so all samples reach `f`.
+    fn seed(
+        &mut self,
+        forced: bool,
+        f: &mut dyn FnMut(u64, Frame) -> Result<(), SeedError>,
+    ) -> Result<(), SeedError>;
     /// Return true if opus
     ///
     /// Run after seeding as we do not have guarantee that
     /// seeder has this data on init.
     fn is_opus(&self) -> bool;
 
-    fn info(&self) -> AudioInfo;
+    fn info(&self) -> &AudioInfo;
+
+    /// Number of samples (per channel) decoded so far
+    fn samples(&self) -> u64;
+
+    /// Damage found while seeding
+    fn corruption(&self) -> &Corruption;
+
+    /// Messages that decoder emitted about this file
+    fn diagnostics(&self) -> &[Diagnostic];
+}
+
+/// Damage found while decoding.
+///
+/// Bad packets are only skipped with forced seeding, otherwise first one is returned as error.
+#[derive(Debug, Clone, Default)]
+pub struct Corruption {
+    /// Number of packets that could not be decoded
+    pub errors: usize,
+    /// Bad regions as (start, end) in seconds (adjacent bad packets are merged)
+    pub regions: Vec<(f64, f64)>,
+    /// Duration of audio (in seconds) that was skipped
+    pub missing: f64,
+}
+
+impl Corruption {
+    pub fn is_damaged(&self) -> bool {
+        self.errors > 0
+    }
+
+    /// Record bad packet
+    pub(crate) fn add(&mut self, start: f64, duration: f64) {
+        self.errors += 1;
+        self.missing += duration;
+        let end = start + duration;
+        match self.regions.last_mut() {
+            // allow some jitter in timestamps
+            Some(last) if start <= last.1 + 0.001 => last.1 = last.1.max(end),
+            _ => self.regions.push((start, end)),
+        }
+    }
+}
+
+impl Display for Corruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} bad packets, {:.3}s missing at",
+            self.errors, self.missing
+        )?;
+        for (i, (start, end)) in self.regions.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{sep} {start:.3}s-{end:.3}s")?;
+        }
+        Ok(())
+    }
 }
 
+/// Technical stream metadata
+#[derive(Debug, Clone)]
 pub struct AudioInfo {
     pub rate: u32,
     pub channels: u32,
+    /// Duration in seconds as declared by container (can be estimated or wrong)
+    pub duration: Option<f64>,
+    /// Codec name (like `flac` or `mp3`)
+    pub codec: String,
+    /// Container format name (like `flac` or `ogg`)
+    pub container: Option<String>,
+    /// Bits per raw sample (only for lossless codecs)
+    pub bits_per_raw_sample: Option<u32>,
+    /// Bitrate in bits per second
+    pub bitrate: Option<u64>,
+    /// Channel layout name (like `stereo` or `5.1`)
+    pub channel_layout: Option<String>,
+    /// Encoder tag (like `LAME3.100`)
+    pub encoder: Option<String>,
+}
+
+#[cfg(test)]
+impl AudioInfo {
+    /// Minimal info for analyzer tests
+    pub(crate) fn for_test(rate: u32, channels: u32, codec: &str) -> Self {
+        Self {
+            rate,
+            channels,
+            duration: None,
+            codec: codec.to_owned(),
+            container: None,
+            bits_per_raw_sample: None,
+            bitrate: None,
+            channel_layout: None,
+            encoder: None,
+        }
+    }
 }
 
+impl Display for AudioInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.codec)?;
+        if let Some(container) = &self.container {
+            write!(f, " in {container}")?;
+        }
+        write!(f, ", {} Hz", self.rate)?;
+        match &self.channel_layout {
+            Some(layout) => write!(f, ", {layout}")?,
+            None => write!(f, ", {} channels", self.channels)?,
+        }
+        if let Some(bits) = self.bits_per_raw_sample {
+            write!(f, ", {bits} bit")?;
+        }
+        if let Some(bitrate) = self.bitrate {
+            write!(f, ", {} kb/s", bitrate / 1000)?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, ", {duration:.3}s")?;
+        }
+        if let Some(encoder) = &self.encoder {
+            write!(f, ", encoder: {encoder}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum FrameType<'a, T> {
     Packed(&'a [T]),
     Planar(&'a [&'a [T]]),
 }
 
+/// Decoded samples as they come from decoder
+#[derive(Clone, Copy)]
 pub enum Frame<'a> {
     I16(FrameType<'a, i16>),
     I32(FrameType<'a, i32>),
     F32(FrameType<'a, f32>),
     F64(FrameType<'a, f64>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacent_bad_packets_are_merged() {
+        let mut corruption = Corruption::default();
+        assert!(!corruption.is_damaged());
+        corruption.add(1.0, 0.5);
+        corruption.add(1.5, 0.25);
+        // timestamp jitter
+        corruption.add(1.7504, 0.25);
+        corruption.add(3.0, 0.5);
+        assert!(corruption.is_damaged());
+        assert_eq!(corruption.errors, 4);
+        assert_eq!(corruption.regions.len(), 2);
+        assert_eq!(corruption.regions[0].0, 1.0);
+        assert!((corruption.regions[0].1 - 2.0004).abs() < 1e-9);
+        assert_eq!(corruption.regions[1], (3.0, 3.5));
+        assert!((corruption.missing - 1.5).abs() < 1e-9);
+        assert_eq!(
+            corruption.to_string(),
+            "4 bad packets, 1.500s missing at 1.000s-2.000s, 3.000s-3.500s"
+        );
+    }
+}
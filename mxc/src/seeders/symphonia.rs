@@ -0,0 +1,208 @@
+use std::fs::File;
+use std::path::Path;
+
+use log::{debug, info, warn};
+use symphonia::core::audio::{AudioBufferRef, Layout, SampleBuffer};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{Metadata, MetadataOptions, StandardTagKey};
+use symphonia::core::probe::Hint;
+use symphonia::core::units::TimeBase;
+
+use crate::diagnostics::Diagnostic;
+use crate::error::{NError, SeedError};
+use crate::seeders::{AudioInfo, Corruption, Frame, FrameType, Seeder};
+
+/// Pure Rust decoder
+///
+/// Supports FLAC, MP3, AAC, ALAC, Vorbis, WAV and AIFF.
+pub struct SymphoniaSeeder {
+    file: String,
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    info: AudioInfo,
+    /// decoded so far
+    samples: u64,
+    time_base: TimeBase,
+    corruption: Corruption,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Encoder tag from latest metadata revision
+fn encoder(metadata: &Metadata) -> Option<String> {
+    metadata
+        .current()?
+        .tags()
+        .iter()
+        .find(|t| t.std_key == Some(StandardTagKey::Encoder))
+        .map(|t| t.value.to_string())
+}
+
+impl SymphoniaSeeder {
+    pub fn new(path: &Path) -> Result<(Self, u64), NError> {
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext);
+        }
+        let mss = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+        let mut probed = symphonia::default::get_probe().format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let mut format = probed.format;
+        // encoder can be in container or in tags before it (like ID3v2)
+        let encoder =
+            encoder(&format.metadata()).or_else(|| probed.metadata.get().and_then(|m| encoder(&m)));
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| NError::Unsupported("no audio track".to_owned()))?;
+        let params = &track.codec_params;
+        // we do not have opus decoder, so ffmpeg must do it
+        if params.codec == CODEC_TYPE_OPUS {
+            return Err(NError::Unsupported("opus".to_owned()));
+        }
+        let rate = params
+            .sample_rate
+            .ok_or_else(|| NError::Unsupported("unknown sample rate".to_owned()))?;
+        let channels = params
+            .channels
+            .ok_or_else(|| NError::Unsupported("unknown channels".to_owned()))?
+            .count() as u32;
+        let n_frames = params.n_frames;
+        let time_base = params.time_base.unwrap_or_else(|| TimeBase::new(1, rate));
+        let info = AudioInfo {
+            rate,
+            channels,
+            duration: n_frames.map(|n| n as f64 / f64::from(rate)),
+            codec: symphonia::default::get_codecs()
+                .get_codec(params.codec)
+                .map_or_else(|| params.codec.to_string(), |c| c.short_name.to_owned()),
+            // symphonia does not name formats
+            container: None,
+            bits_per_raw_sample: params.bits_per_sample,
+            bitrate: None,
+            channel_layout: params.channel_layout.map(|l| {
+                match l {
+                    Layout::Mono => "mono",
+                    Layout::Stereo => "stereo",
+                    Layout::TwoPointOne => "2.1",
+                    Layout::FivePointOne => "5.1",
+                }
+                .to_owned()
+            }),
+            encoder,
+        };
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs().make(params, &DecoderOptions::default())?;
+        Ok((
+            Self {
+                file: path.as_os_str().to_string_lossy().to_string(),
+                format,
+                decoder,
+                track_id,
+                info,
+                samples: 0,
+                time_base,
+                corruption: Corruption::default(),
+                diagnostics: Vec::new(),
+            },
+            n_frames.unwrap_or_default(),
+        ))
+    }
+}
+
+impl Seeder for SymphoniaSeeder {
+    fn seed(
+        &mut self,
+        forced: bool,
+        f: &mut dyn FnMut(u64, Frame) -> Result<(), SeedError>,
+    ) -> Result<(), SeedError> {
+        info!(
+            "{} {} channels {}Hz",
+            self.file, self.info.channels, self.info.rate
+        );
+
+        // used for sample formats that libs cannot handle
+        let mut converted: Option<SampleBuffer<i32>> = None;
+
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                // this is how symphonia says end of stream
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(e)) if forced => {
+                    warn!("Skipping damaged packet in {}: {e}", self.file);
+                    self.diagnostics.push(Diagnostic {
+                        level: log::Level::Error,
+                        component: Some("symphonia".to_owned()),
+                        message: e.to_owned(),
+                    });
+                    let start = self.time_base.calc_time(packet.ts());
+                    let duration = self.time_base.calc_time(packet.dur());
+                    self.corruption.add(
+                        start.seconds as f64 + start.frac,
+                        duration.seconds as f64 + duration.frac,
+                    );
+                    continue;
+                }
+                Err(Error::DecodeError(e)) => return Err(SeedError::Damaged(e.to_owned())),
+                Err(e) => return Err(e.into()),
+            };
+            self.samples += decoded.frames() as u64;
+            let d = self.samples;
+            match decoded {
+                AudioBufferRef::S16(x) => f(d, Frame::I16(FrameType::Planar(x.planes().planes()))),
+                AudioBufferRef::S32(x) => f(d, Frame::I32(FrameType::Planar(x.planes().planes()))),
+                AudioBufferRef::F32(x) => f(d, Frame::F32(FrameType::Planar(x.planes().planes()))),
+                AudioBufferRef::F64(x) => f(d, Frame::F64(FrameType::Planar(x.planes().planes()))),
+                // libs cannot handle them so we need to convert
+                decoded => {
+                    let buf = converted.get_or_insert_with(|| {
+                        debug!("Conversion will be used!");
+                        SampleBuffer::new(decoded.capacity() as u64, *decoded.spec())
+                    });
+                    buf.copy_interleaved_ref(decoded);
+                    f(d, Frame::I32(FrameType::Packed(buf.samples())))
+                }
+            }?;
+        }
+
+        // symphonia decoders do not hold back any frames, so there is nothing to drain
+        Ok(())
+    }
+
+    fn is_opus(&self) -> bool {
+        // opus is always handled by ffmpeg
+        false
+    }
+
+    fn info(&self) -> &AudioInfo {
+        &self.info
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    fn corruption(&self) -> &Corruption {
+        &self.corruption
+    }
+
+    fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
@@ -1,12 +1,585 @@
-use crate::tagger::Tagger;
+use std::path::{Path, PathBuf};
 
-pub struct Lofty {}
+use lofty::ape::{ApeFile, ApeItem, ApeTag};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::error::LoftyError;
+use lofty::file::{AudioFile, FileType, TaggedFileExt};
+use lofty::flac::FlacFile;
+use lofty::id3::v2::{Frame, Id3v2Tag};
+use lofty::iff::aiff::AiffFile;
+use lofty::iff::wav::WavFile;
+use lofty::mp4::{Atom, AtomData, AtomIdent, Ilst, Mp4File};
+use lofty::mpeg::MpegFile;
+use lofty::ogg::{OpusFile, SpeexFile, VorbisComments, VorbisFile};
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, TagExt, TagType};
+use lofty::wavpack::WavPackFile;
+
+use crate::error::MetaError;
+use crate::options::Id3v2version;
+use crate::replay_gain::{opus_gain, ReplayGain};
+use crate::taggers::{Tagger, WriteOptions as RgWriteOptions};
+
+// possible replaygain tags (same as in taglibxx)
+const RG_TRACK_GAIN: usize = 0;
+const RG_TRACK_PEAK: usize = 1;
+const RG_TRACK_RANGE: usize = 2;
+const RG_ALBUM_GAIN: usize = 3;
+const RG_ALBUM_PEAK: usize = 4;
+const RG_ALBUM_RANGE: usize = 5;
+const RG_REFERENCE_LOUDNESS: usize = 6;
+
+const RG_STRING_UPPER: [&str; 7] = [
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_TRACK_RANGE",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+    "REPLAYGAIN_ALBUM_RANGE",
+    "REPLAYGAIN_REFERENCE_LOUDNESS",
+];
+
+const RG_STRING_LOWER: [&str; 7] = [
+    "replaygain_track_gain",
+    "replaygain_track_peak",
+    "replaygain_track_range",
+    "replaygain_album_gain",
+    "replaygain_album_peak",
+    "replaygain_album_range",
+    "replaygain_reference_loudness",
+];
+
+const R128_TRACK_GAIN: &str = "R128_TRACK_GAIN";
+const R128_ALBUM_GAIN: &str = "R128_ALBUM_GAIN";
+
+// this is where we store the RG tags in MP4/M4A files
+const RG_ATOM_MEAN: &str = "com.apple.iTunes";
+
+fn is_rg(key: &str) -> bool {
+    RG_STRING_UPPER.iter().any(|x| x.eq_ignore_ascii_case(key))
+}
+
+/// RG values that are to be written
+struct Rg {
+    track: ReplayGain,
+    album: Option<ReplayGain>,
+    extended: bool,
+    unit: String,
+    lowercase: bool,
+    non_standard_opus: bool,
+}
+
+impl Rg {
+    /// Builds (key, value) pairs in the same order and format as taglibxx
+    fn items(&self, lowercase: bool) -> Vec<(&'static str, String)> {
+        let keys = if lowercase {
+            &RG_STRING_LOWER
+        } else {
+            &RG_STRING_UPPER
+        };
+        let unit = &self.unit;
+        let mut items = vec![
+            (
+                keys[RG_TRACK_GAIN],
+                format!("{:.2} {unit}", self.track.gain),
+            ),
+            (keys[RG_TRACK_PEAK], format!("{:.6}", self.track.peak)),
+        ];
+        // Only write album tags if in album mode (would be zero otherwise)
+        if let Some(album) = self.album {
+            items.push((keys[RG_ALBUM_GAIN], format!("{:.2} {unit}", album.gain)));
+            items.push((keys[RG_ALBUM_PEAK], format!("{:.6}", album.peak)));
+        }
+        // extra tags mode
+        if self.extended {
+            items.push((
+                keys[RG_REFERENCE_LOUDNESS],
+                format!("{:.2} LUFS", self.track.loudness_reference),
+            ));
+            items.push((
+                keys[RG_TRACK_RANGE],
+                format!("{:.2} {unit}", self.track.loudness_range),
+            ));
+            if let Some(album) = self.album {
+                items.push((
+                    keys[RG_ALBUM_RANGE],
+                    format!("{:.2} {unit}", album.loudness_range),
+                ));
+            }
+        }
+        items
+    }
+}
+
+// None means delete
+fn do_id3v2(tag: &mut Id3v2Tag, rg: Option<&Rg>) {
+    // this removes all variants of upper-/lower-/mixed-case tags
+    tag.retain(|frame| !matches!(frame, Frame::UserText(f) if is_rg(&f.description)));
+    if let Some(rg) = rg {
+        for (k, v) in rg.items(rg.lowercase) {
+            tag.insert_user_text(k.to_owned(), v);
+        }
+    }
+}
+
+fn do_vorbis(tag: &mut VorbisComments, rg: Option<&Rg>) {
+    for key in RG_STRING_UPPER {
+        let _ = tag.remove(key);
+    }
+    if let Some(rg) = rg {
+        for (k, v) in rg.items(false) {
+            tag.push(k.to_owned(), v);
+        }
+    }
+}
+
+fn do_opus(tag: &mut VorbisComments, rg: Option<&Rg>) {
+    // RFC 7845: Opus comment header SHOULD NOT contain any of the REPLAYGAIN_* tags
+    do_vorbis(tag, None);
+    let _ = tag.remove(R128_TRACK_GAIN);
+    let _ = tag.remove(R128_ALBUM_GAIN);
+    match rg {
+        None => {}
+        Some(rg) if rg.non_standard_opus => do_vorbis(tag, Some(rg)),
+        // Opus only knows gains as Q7.8 numbers and no extended tags
+        Some(rg) => {
+            tag.push(
+                R128_TRACK_GAIN.to_owned(),
+                opus_gain(rg.track.gain).to_string(),
+            );
+            if let Some(album) = rg.album {
+                tag.push(
+                    R128_ALBUM_GAIN.to_owned(),
+                    opus_gain(album.gain).to_string(),
+                );
+            }
+        }
+    }
+}
+
+fn do_mp4(tag: &mut Ilst, rg: Option<&Rg>) {
+    tag.retain(|atom| {
+        !matches!(atom.ident(), AtomIdent::Freeform { mean, name } if mean == RG_ATOM_MEAN && is_rg(name))
+    });
+    if let Some(rg) = rg {
+        for (k, v) in rg.items(rg.lowercase) {
+            tag.insert(Atom::new(
+                AtomIdent::Freeform {
+                    mean: RG_ATOM_MEAN.into(),
+                    name: k.into(),
+                },
+                AtomData::UTF8(v),
+            ));
+        }
+    }
+}
+
+// APEv2 tags can be mixed case, but they should be read case-insensitively,
+// so we ignore lowercase (same as taglibxx).
+fn do_ape(tag: &mut ApeTag, rg: Option<&Rg>) -> Result<(), MetaError> {
+    for key in RG_STRING_UPPER {
+        tag.remove(key);
+    }
+    if let Some(rg) = rg {
+        for (k, v) in rg.items(false) {
+            tag.insert(ApeItem::new(k.to_owned(), ItemValue::Text(v))?);
+        }
+    }
+    Ok(())
+}
+
+// custom text tags are (key, value), `None` value means delete
+fn is_custom(tags: &[(&str, Option<String>)], key: &str) -> bool {
+    tags.iter().any(|(k, _)| k.eq_ignore_ascii_case(key))
+}
+
+fn custom_id3v2(tag: &mut Id3v2Tag, tags: &[(&str, Option<String>)]) {
+    tag.retain(|frame| !matches!(frame, Frame::UserText(f) if is_custom(tags, &f.description)));
+    for (k, v) in tags {
+        if let Some(v) = v {
+            tag.insert_user_text((*k).to_owned(), v.clone());
+        }
+    }
+}
+
+fn custom_vorbis(tag: &mut VorbisComments, tags: &[(&str, Option<String>)]) {
+    for (k, v) in tags {
+        let _ = tag.remove(k);
+        if let Some(v) = v {
+            tag.push(k.to_ascii_uppercase(), v.clone());
+        }
+    }
+}
+
+fn custom_mp4(tag: &mut Ilst, tags: &[(&str, Option<String>)]) {
+    tag.retain(|atom| {
+        !matches!(atom.ident(), AtomIdent::Freeform { mean, name } if mean == RG_ATOM_MEAN && is_custom(tags, name))
+    });
+    for (k, v) in tags {
+        if let Some(v) = v {
+            tag.insert(Atom::new(
+                AtomIdent::Freeform {
+                    mean: RG_ATOM_MEAN.into(),
+                    name: (*k).to_owned().into(),
+                },
+                AtomData::UTF8(v.clone()),
+            ));
+        }
+    }
+}
+
+// freeform atoms have no generic item key, so they are looked up by name
+fn read_mp4(tag: &Ilst, key: &str) -> Option<String> {
+    tag.into_iter()
+        .find(|atom| {
+            matches!(atom.ident(), AtomIdent::Freeform { mean, name } if mean == RG_ATOM_MEAN && name.eq_ignore_ascii_case(key))
+        })
+        .and_then(|atom| {
+            atom.data().find_map(|data| match data {
+                AtomData::UTF8(v) | AtomData::UTF16(v) => Some(v.clone()),
+                _ => None,
+            })
+        })
+}
+
+fn custom_ape(tag: &mut ApeTag, tags: &[(&str, Option<String>)]) -> Result<(), MetaError> {
+    for (k, v) in tags {
+        tag.remove(k);
+        if let Some(v) = v {
+            tag.insert(ApeItem::new(
+                k.to_ascii_uppercase(),
+                ItemValue::Text(v.clone()),
+            )?);
+        }
+    }
+    Ok(())
+}
+
+/// Pure Rust tagger that produces same tag layout as taglibxx.
+///
+/// ASF/WMA and Ogg FLAC are not supported by lofty.
+pub struct Lofty {
+    file: PathBuf,
+}
 
 impl Lofty {
     /// Creates a new [`Lofty`].
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(path: &Path) -> Self {
+        Self {
+            file: path.to_path_buf(),
+        }
+    }
+
+    fn read<F: AudioFile>(&self) -> Result<F, MetaError> {
+        let mut f = std::fs::File::open(&self.file).map_err(|e| MetaError::Internal(e.into()))?;
+        Ok(F::read_from(
+            &mut f,
+            ParseOptions::new().read_properties(false),
+        )?)
+    }
+
+    fn save<T>(&self, tag: &T, id3v2version: Id3v2version) -> Result<(), MetaError>
+    where
+        T: TagExt<Err = LoftyError>,
+    {
+        Ok(tag.save_to_path(
+            &self.file,
+            WriteOptions::default().use_id3v23(id3v2version == Id3v2version::V3),
+        )?)
+    }
+
+    fn file_type(&self) -> Result<Option<FileType>, MetaError> {
+        Ok(Probe::open(&self.file)?
+            .guess_file_type()
+            .map_err(|e| MetaError::Internal(e.into()))?
+            .file_type())
+    }
+
+    fn strip(&self, tag_types: &[TagType]) -> Result<(), MetaError> {
+        for t in tag_types {
+            t.remove_from_path(&self.file)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tagger for Lofty {
+    fn do_meta(
+        &self,
+        strip: bool,
+        id3v2version: Id3v2version,
+        write: Option<RgWriteOptions>,
+        track: Option<ReplayGain>,
+        album: Option<ReplayGain>,
+    ) -> Result<(), MetaError> {
+        let rg = if let Some(wopts) = write {
+            Some(Rg {
+                track: track.ok_or(MetaError::NotComputed)?,
+                album,
+                extended: wopts.extended,
+                unit: wopts.unit,
+                lowercase: wopts.lowercase,
+                non_standard_opus: wopts.non_standard_opus,
+            })
+        } else {
+            None
+        };
+        let rg = rg.as_ref();
+
+        match self.file_type()? {
+            Some(FileType::Mpeg) => {
+                let mut tag = self
+                    .read::<MpegFile>()?
+                    .id3v2()
+                    .cloned()
+                    .unwrap_or_default();
+                do_id3v2(&mut tag, rg);
+                if strip {
+                    self.strip(&[TagType::Ape, TagType::Id3v1])?;
+                }
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Wav) => {
+                let mut tag = self.read::<WavFile>()?.id3v2().cloned().unwrap_or_default();
+                do_id3v2(&mut tag, rg);
+                // no stripping
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Aiff) => {
+                let mut tag = self
+                    .read::<AiffFile>()?
+                    .id3v2()
+                    .cloned()
+                    .unwrap_or_default();
+                do_id3v2(&mut tag, rg);
+                // no stripping
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Flac) => {
+                let mut tag = self
+                    .read::<FlacFile>()?
+                    .vorbis_comments()
+                    .cloned()
+                    .unwrap_or_default();
+                do_vorbis(&mut tag, rg);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Vorbis) => {
+                let mut tag = self.read::<VorbisFile>()?.vorbis_comments().clone();
+                do_vorbis(&mut tag, rg);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Speex) => {
+                let mut tag = self.read::<SpeexFile>()?.vorbis_comments().clone();
+                do_vorbis(&mut tag, rg);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Opus) => {
+                let mut tag = self.read::<OpusFile>()?.vorbis_comments().clone();
+                do_opus(&mut tag, rg);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Mp4) => {
+                let mut tag = self.read::<Mp4File>()?.ilst().cloned().unwrap_or_default();
+                do_mp4(&mut tag, rg);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::WavPack) => {
+                let mut tag = self
+                    .read::<WavPackFile>()?
+                    .ape()
+                    .cloned()
+                    .unwrap_or_default();
+                do_ape(&mut tag, rg)?;
+                if strip {
+                    self.strip(&[TagType::Id3v1])?;
+                }
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Ape) => {
+                let mut tag = self.read::<ApeFile>()?.ape().cloned().unwrap_or_default();
+                do_ape(&mut tag, rg)?;
+                if strip {
+                    self.strip(&[TagType::Id3v1])?;
+                }
+                self.save(&tag, id3v2version)?;
+            }
+            Some(x) => return Err(MetaError::Unsupported(format!("{x:?}"))),
+            None => {
+                return Err(MetaError::Unsupported(
+                    self.file.to_string_lossy().to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn do_custom(
+        &self,
+        id3v2version: Id3v2version,
+        tags: &[(&str, Option<String>)],
+    ) -> Result<(), MetaError> {
+        match self.file_type()? {
+            Some(FileType::Mpeg) => {
+                let mut tag = self
+                    .read::<MpegFile>()?
+                    .id3v2()
+                    .cloned()
+                    .unwrap_or_default();
+                custom_id3v2(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Wav) => {
+                let mut tag = self.read::<WavFile>()?.id3v2().cloned().unwrap_or_default();
+                custom_id3v2(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Aiff) => {
+                let mut tag = self
+                    .read::<AiffFile>()?
+                    .id3v2()
+                    .cloned()
+                    .unwrap_or_default();
+                custom_id3v2(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Flac) => {
+                let mut tag = self
+                    .read::<FlacFile>()?
+                    .vorbis_comments()
+                    .cloned()
+                    .unwrap_or_default();
+                custom_vorbis(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Vorbis) => {
+                let mut tag = self.read::<VorbisFile>()?.vorbis_comments().clone();
+                custom_vorbis(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Speex) => {
+                let mut tag = self.read::<SpeexFile>()?.vorbis_comments().clone();
+                custom_vorbis(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Opus) => {
+                let mut tag = self.read::<OpusFile>()?.vorbis_comments().clone();
+                custom_vorbis(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Mp4) => {
+                let mut tag = self.read::<Mp4File>()?.ilst().cloned().unwrap_or_default();
+                custom_mp4(&mut tag, tags);
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::WavPack) => {
+                let mut tag = self
+                    .read::<WavPackFile>()?
+                    .ape()
+                    .cloned()
+                    .unwrap_or_default();
+                custom_ape(&mut tag, tags)?;
+                self.save(&tag, id3v2version)?;
+            }
+            Some(FileType::Ape) => {
+                let mut tag = self.read::<ApeFile>()?.ape().cloned().unwrap_or_default();
+                custom_ape(&mut tag, tags)?;
+                self.save(&tag, id3v2version)?;
+            }
+            Some(x) => return Err(MetaError::Unsupported(format!("{x:?}"))),
+            None => {
+                return Err(MetaError::Unsupported(
+                    self.file.to_string_lossy().to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn read_custom(&self, keys: &[&str]) -> Result<Vec<Option<String>>, MetaError> {
+        let tagged = Probe::open(&self.file)?
+            .options(ParseOptions::new().read_properties(false))
+            .read()?;
+        let ilst = if tagged.file_type() == FileType::Mp4 {
+            self.read::<Mp4File>()?.ilst().cloned()
+        } else {
+            None
+        };
+        // generic keys are mapped back to Vorbis names (that are used by taglibxx)
+        Ok(keys
+            .iter()
+            .map(|key| {
+                if let Some(v) = ilst.as_ref().and_then(|tag| read_mp4(tag, key)) {
+                    return Some(v);
+                }
+                tagged.tags().iter().find_map(|tag| {
+                    tag.items()
+                        .find(|item| {
+                            item.key()
+                                .map_key(TagType::VorbisComments, true)
+                                .is_some_and(|k| k.eq_ignore_ascii_case(key))
+                        })
+                        .and_then(|item| item.value().text())
+                        .map(str::to_owned)
+                })
+            })
+            .collect())
     }
 }
 
-impl Tagger for Lofty {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(payload);
+        b
+    }
+
+    // smallest M4A that lofty accepts: a file type and a movie header without tracks
+    fn empty_m4a() -> Vec<u8> {
+        let mut ftyp = b"M4A ".to_vec();
+        ftyp.extend_from_slice(&0u32.to_be_bytes());
+        ftyp.extend_from_slice(b"M4A isom");
+        let mut mvhd = vec![0; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes()); // timescale
+        mvhd[20..24].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // rate
+        mvhd[24..26].copy_from_slice(&0x0100u16.to_be_bytes()); // volume
+        mvhd[36..40].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // identity matrix
+        mvhd[52..56].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        mvhd[68..72].copy_from_slice(&0x4000_0000u32.to_be_bytes());
+        mvhd[96..100].copy_from_slice(&1u32.to_be_bytes()); // next track id
+        let mut file = mp4_box(b"ftyp", &ftyp);
+        file.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+        file
+    }
+
+    #[test]
+    fn mp4_custom_tags_read_back() {
+        let path = std::env::temp_dir().join(format!("mxc-lofty-{}.m4a", std::process::id()));
+        std::fs::write(&path, empty_m4a()).unwrap();
+        let tagger = Lofty::new(&path);
+        let keys = ["DYNAMIC RANGE", "album dynamic range", "ARTIST"];
+
+        tagger
+            .do_custom(
+                Id3v2version::V4,
+                &[
+                    ("DYNAMIC RANGE", Some("12".to_owned())),
+                    ("ALBUM DYNAMIC RANGE", Some("11".to_owned())),
+                ],
+            )
+            .unwrap();
+        let read = tagger.read_custom(&keys).unwrap();
+        assert_eq!(read, [Some("12".to_owned()), Some("11".to_owned()), None]);
+
+        tagger
+            .do_custom(Id3v2version::V4, &[("ALBUM DYNAMIC RANGE", None)])
+            .unwrap();
+        let read = tagger.read_custom(&keys).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, [Some("12".to_owned()), None, None]);
+    }
+}
@@ -1,6 +1,15 @@
 use crate::error::MetaError;
 use crate::replay_gain::ReplayGain;
 
+#[cfg(feature = "lofty")]
+mod lofty;
+#[cfg(feature = "lofty")]
+pub use self::lofty::Lofty;
+#[cfg(feature = "taglib")]
+mod taglib;
+#[cfg(feature = "taglib")]
+pub use self::taglib::TagLib;
+
 pub struct WriteOptions {
     pub extended: bool,
     pub unit: String,
@@ -17,4 +26,14 @@ pub trait Tagger {
         track: Option<ReplayGain>,
         album: Option<ReplayGain>,
     ) -> Result<(), MetaError>;
+
+    /// Writes custom text tags (like DR score), tags with `None` value are removed
+    fn do_custom(
+        &self,
+        id3v2version: crate::options::Id3v2version,
+        tags: &[(&str, Option<String>)],
+    ) -> Result<(), MetaError>;
+
+    /// Reads custom text tags (case insensitive), `None` if tag is missing
+    fn read_custom(&self, keys: &[&str]) -> Result<Vec<Option<String>>, MetaError>;
 }
@@ -0,0 +1,305 @@
+use std::path::Path;
+
+use ffmpeg::codec;
+use ffmpeg_next as ffmpeg;
+use taglibxx as taglib;
+
+use crate::error::{MetaError, NError};
+use crate::taggers::Tagger;
+
+#[allow(clippy::upper_case_acronyms)]
+enum AvContainer {
+    MP3,
+    FLAC,
+    OGG,
+    MP4,
+    ASF,
+    WAV,
+    WV,
+    AIFF,
+    APE,
+    Unsupported(String),
+}
+
+impl AvContainer {
+    fn new(s: &str) -> AvContainer {
+        // FFmpeg container short names
+        match s {
+            "mp3" => AvContainer::MP3,
+            "flac" => AvContainer::FLAC,
+            "ogg" => AvContainer::OGG,
+            //"mov" | "mp4" | "m4a" | "3gp" | "3g2" | "mj2" => AvContainer::MP4,
+            "mov,mp4,m4a,3gp,3g2,mj2" => AvContainer::MP4,
+            "asf" => AvContainer::ASF,
+            "wav" => AvContainer::WAV,
+            "wv" => AvContainer::WV,
+            "aiff" => AvContainer::AIFF,
+            "ape" => AvContainer::APE,
+            _ => AvContainer::Unsupported(s.to_owned()),
+        }
+    }
+}
+
+/// TagLib tagger (container is detected with FFmpeg)
+pub struct TagLib {
+    file: String,
+    container: AvContainer,
+    codec_id: codec::Id,
+}
+
+impl TagLib {
+    pub fn new(path: &Path) -> Result<Self, NError> {
+        crate::fftag::maybe_init()?;
+        let ictx = ffmpeg::format::input(&path)?;
+        let input = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Audio)
+            .ok_or(ffmpeg::Error::StreamNotFound)?;
+        Ok(Self {
+            file: path.as_os_str().to_string_lossy().to_string(),
+            container: AvContainer::new(ictx.format().name()),
+            codec_id: input.parameters().id(),
+        })
+    }
+}
+
+impl From<crate::replay_gain::ReplayGain> for taglib::ReplayGain {
+    fn from(x: crate::replay_gain::ReplayGain) -> Self {
+        Self {
+            gain: x.gain,
+            peak: x.peak,
+            loudness_range: x.loudness_range,
+            loudness_reference: x.loudness_reference,
+            loudness: x.loudness,
+        }
+    }
+}
+
+impl Tagger for TagLib {
+    fn do_meta(
+        &self,
+        strip: bool,
+        id3v2version: crate::options::Id3v2version,
+        write: Option<crate::taggers::WriteOptions>,
+        track: Option<crate::replay_gain::ReplayGain>,
+        album: Option<crate::replay_gain::ReplayGain>,
+    ) -> Result<(), MetaError> {
+        use taglib::*;
+        if let Some(wopts) = write {
+            let scan = Scan {
+                file: self.file.clone(),
+                track: track.ok_or(MetaError::NotComputed)?.into(),
+                album: album.unwrap_or_default().into(),
+            };
+            let do_album = album.is_some();
+            let extended = wopts.extended;
+            let unit = wopts.unit;
+            let lowercase = wopts.lowercase;
+            let non_standard_opus = wopts.non_standard_opus;
+            let id3v2version = id3v2version as i32;
+            // write tags
+            match &self.container {
+                AvContainer::MP3 => {
+                    if !tag_write_mp3(
+                        scan,
+                        do_album,
+                        extended,
+                        unit,
+                        lowercase,
+                        strip,
+                        id3v2version,
+                    ) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::FLAC => {
+                    if !tag_write_flac(scan, do_album, extended, unit) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::OGG => match self.codec_id {
+                    codec::Id::OPUS => {
+                        if non_standard_opus {
+                            if !tag_write_ogg_opus_non_standard(scan, do_album, extended, unit) {
+                                return Err(MetaError::Write(self.file.clone()));
+                            }
+                        } else if !tag_write_ogg_opus(scan, do_album, extended, unit) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    codec::Id::VORBIS => {
+                        if !tag_write_ogg_vorbis(scan, do_album, extended, unit) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    codec::Id::FLAC => {
+                        if !tag_write_ogg_flac(scan, do_album, extended, unit) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    codec::Id::SPEEX => {
+                        if !tag_write_ogg_speex(scan, do_album, extended, unit) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    _ => return Err(MetaError::Unsupported(self.codec_id.name().to_owned())),
+                },
+                AvContainer::MP4 => {
+                    if !tag_write_mp4(scan, do_album, extended, unit, lowercase) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::ASF => {
+                    if !tag_write_asf(scan, do_album, extended, unit, lowercase) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::WAV => {
+                    if !tag_write_wav(
+                        scan,
+                        do_album,
+                        extended,
+                        unit,
+                        lowercase,
+                        strip,
+                        id3v2version,
+                    ) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::WV => {
+                    if !tag_write_wavpack(scan, do_album, extended, unit, lowercase, strip) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::AIFF => {
+                    if !tag_write_aiff(
+                        scan,
+                        do_album,
+                        extended,
+                        unit,
+                        lowercase,
+                        strip,
+                        id3v2version,
+                    ) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::APE => {
+                    if !tag_write_ape(scan, do_album, extended, unit, lowercase, strip) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::Unsupported(s) => return Err(MetaError::Unsupported(s.clone())),
+            }
+        } else {
+            // delete tags
+            match &self.container {
+                AvContainer::MP3 => {
+                    if !tag_clear_mp3(self.file.clone(), strip, id3v2version as i32) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::FLAC => {
+                    if !tag_clear_flac(self.file.clone()) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::OGG => match self.codec_id {
+                    codec::Id::OPUS => {
+                        if !tag_clear_ogg_opus(self.file.clone()) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    codec::Id::VORBIS => {
+                        if !tag_clear_ogg_vorbis(self.file.clone()) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    codec::Id::FLAC => {
+                        if !tag_clear_ogg_flac(self.file.clone()) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    codec::Id::SPEEX => {
+                        if !tag_clear_ogg_speex(self.file.clone()) {
+                            return Err(MetaError::Write(self.file.clone()));
+                        }
+                    }
+                    _ => return Err(MetaError::Unsupported(self.codec_id.name().to_owned())),
+                },
+                AvContainer::MP4 => {
+                    if !tag_clear_mp4(self.file.clone()) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::ASF => {
+                    if !tag_clear_asf(self.file.clone()) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::WAV => {
+                    if !tag_clear_wav(self.file.clone(), strip, id3v2version as i32) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::WV => {
+                    if !tag_clear_wavpack(self.file.clone(), strip) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::AIFF => {
+                    if !tag_clear_aiff(self.file.clone(), strip, id3v2version as i32) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::APE => {
+                    if !tag_clear_ape(self.file.clone(), strip) {
+                        return Err(MetaError::Write(self.file.clone()));
+                    }
+                }
+                AvContainer::Unsupported(s) => return Err(MetaError::Unsupported(s.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    fn do_custom(
+        &self,
+        id3v2version: crate::options::Id3v2version,
+        tags: &[(&str, Option<String>)],
+    ) -> Result<(), MetaError> {
+        if let AvContainer::Unsupported(s) = &self.container {
+            return Err(MetaError::Unsupported(s.clone()));
+        }
+        let properties = tags
+            .iter()
+            .map(|(key, value)| taglib::Property {
+                key: (*key).to_owned(),
+                value: value.clone().unwrap_or_default(),
+                remove: value.is_none(),
+            })
+            .collect();
+        if taglib::tag_edit_properties(self.file.clone(), properties, id3v2version as i32) {
+            Ok(())
+        } else {
+            Err(MetaError::Write(self.file.clone()))
+        }
+    }
+
+    fn read_custom(&self, keys: &[&str]) -> Result<Vec<Option<String>>, MetaError> {
+        if let AvContainer::Unsupported(s) = &self.container {
+            return Err(MetaError::Unsupported(s.clone()));
+        }
+        let properties = taglib::tag_read_properties(self.file.clone());
+        Ok(keys
+            .iter()
+            .map(|key| {
+                properties
+                    .iter()
+                    .find(|p| p.key.eq_ignore_ascii_case(key))
+                    .map(|p| p.value.clone())
+            })
+            .collect())
+    }
+}
@@ -18,6 +18,14 @@ pub mod ffi {
         album: ReplayGain,
     }
 
+    /// Custom text tag (like DR score)
+    pub struct Property {
+        key: String,
+        value: String,
+        // remove instead of writing
+        remove: bool,
+    }
+
     unsafe extern "C++" {
         // One or more headers with the matching C++ declarations. Our code
         // generators don't read it but it gets #include'd and used in static
@@ -61,6 +69,9 @@ pub mod ffi {
         fn tag_write_ape(scan: Scan, do_album: bool, extended: bool, unit: String, lowercase: bool, strip: bool) -> bool;
         fn tag_clear_ape(filee: String, strip: bool) -> bool;
 
+        fn tag_edit_properties(filee: String, properties: Vec<Property>, id3v2version: i32) -> bool;
+        fn tag_read_properties(filee: String) -> Vec<Property>;
+
         fn tag_version_major() -> i32;
         fn tag_version_minor() -> i32;
         fn tag_version_patch() -> i32;
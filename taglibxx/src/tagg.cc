@@ -55,6 +55,8 @@
 #include <string.h>
 
 #include <taglib.h>
+#include <fileref.h>
+#include <tpropertymap.h>
 
 #define TAGLIB_VERSION (TAGLIB_MAJOR_VERSION * 10000 + TAGLIB_MINOR_VERSION * 100 + TAGLIB_PATCH_VERSION)
 
@@ -1107,6 +1109,76 @@ bool tag_clear_ape(rust::String filee, bool strip)
     return f.save();
 }
 
+/*** Custom properties (all containers) ***/
+
+// TagLib maps unknown property keys to TXXX frames (ID3v2),
+// fields (Xiph), freeform atoms (MP4), attributes (ASF) and items (APEv2).
+bool tag_edit_properties(rust::String filee, rust::Vec<Property> properties, int id3v2version)
+{
+    TagLib::FileRef f(filee.c_str());
+
+    if (f.isNull())
+        return false;
+
+    TagLib::PropertyMap props = f.file()->properties();
+
+    for (const Property &p : properties)
+    {
+        TagLib::String key = TagLib::String(std::string(p.key), TagLib::String::UTF8).upper();
+
+        if (p.remove)
+            props.erase(key);
+        else
+            props.replace(key, TagLib::StringList(TagLib::String(std::string(p.value), TagLib::String::UTF8)));
+    }
+
+    f.file()->setProperties(props);
+
+    // keep requested ID3v2 version (and do not touch other tags)
+#if TAGLIB_VERSION >= 11200
+    TagLib::ID3v2::Version version = id3v2version == 3 ? TagLib::ID3v2::v3 : TagLib::ID3v2::v4;
+
+    if (TagLib::MPEG::File *mpeg = dynamic_cast<TagLib::MPEG::File *>(f.file()))
+        return mpeg->save(TagLib::MPEG::File::ID3v2, TagLib::MPEG::File::StripNone, version);
+    if (TagLib::RIFF::WAV::File *wav = dynamic_cast<TagLib::RIFF::WAV::File *>(f.file()))
+        return wav->save(TagLib::RIFF::WAV::File::AllTags, TagLib::RIFF::WAV::File::StripNone, version);
+    if (TagLib::RIFF::AIFF::File *aiff = dynamic_cast<TagLib::RIFF::AIFF::File *>(f.file()))
+        return aiff->save(version);
+#else
+    if (TagLib::MPEG::File *mpeg = dynamic_cast<TagLib::MPEG::File *>(f.file()))
+        return mpeg->save(TagLib::MPEG::File::ID3v2, false, id3v2version);
+    if (TagLib::RIFF::WAV::File *wav = dynamic_cast<TagLib::RIFF::WAV::File *>(f.file()))
+        return wav->save(TagLib::RIFF::WAV::File::AllTags, false, id3v2version);
+#endif
+
+    return f.save();
+}
+
+// first value of every property (keys are uppercase)
+rust::Vec<Property> tag_read_properties(rust::String filee)
+{
+    rust::Vec<Property> res;
+    TagLib::FileRef f(filee.c_str());
+
+    if (f.isNull())
+        return res;
+
+    TagLib::PropertyMap props = f.file()->properties();
+
+    for (TagLib::PropertyMap::ConstIterator it = props.begin(); it != props.end(); ++it)
+    {
+        if (it->second.isEmpty())
+            continue;
+
+        res.push_back(Property{
+            rust::String(it->first.to8Bit(true)),
+            rust::String(it->second.front().to8Bit(true)),
+            false});
+    }
+
+    return res;
+}
+
 int tag_version_major()
 {
     return TAGLIB_MAJOR_VERSION;
@@ -77,6 +77,9 @@ bool tag_write_ape(Scan scan, bool do_album, bool extended, rust::String unit,
                    bool lowercase, bool strip);
 bool tag_clear_ape(rust::String filee, bool strip);
 
+bool tag_edit_properties(rust::String filee, rust::Vec<Property> properties, int id3v2version);
+rust::Vec<Property> tag_read_properties(rust::String filee);
+
 int gain_to_q78num(double gain);
 
 int tag_version_major();